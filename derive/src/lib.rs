@@ -0,0 +1,133 @@
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Field, Fields, GenericArgument, LitStr, PathArguments, Type};
+
+// fields of type Arc<T> are resolved by type, #[inject(name = "key")] resolves them by name instead,
+// Option<Arc<T>> fields are left empty when the service is not in the container and
+// #[inject(default)] fills a field that is not a service with Default::default()
+#[proc_macro_derive(Injectable, attributes(inject))]
+pub fn derive_injectable(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+enum Injection {
+    Required(Type, TokenStream),
+    Optional(Type, TokenStream),
+    Default,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => return Err(syn::Error::new(input.ident.span(), "Injectable can only be derived for structs")),
+    };
+    let injections = fields.iter().map(injection).collect::<syn::Result<Vec<_>>>()?;
+
+    let values = injections.iter().zip(fields).map(|(injection, field)| {
+        let span = field.ty.span();
+        match injection {
+            Injection::Required(ty, key) => quote_spanned!(span=> container.resolve_named::<#ty>(#key)?),
+            Injection::Optional(ty, key) => quote_spanned!(span=> container.resolve_optional::<#ty>(#key)?),
+            Injection::Default => quote_spanned!(span=> ::std::default::Default::default()),
+        }
+    });
+    let construct = match fields {
+        Fields::Named(_) => {
+            let names = fields.iter().map(|field| &field.ident);
+            quote!(Self { #(#names: #values),* })
+        }
+        Fields::Unnamed(_) => quote!(Self(#(#values),*)),
+        Fields::Unit => quote!(Self),
+    };
+    let dependencies = injections.iter().filter_map(|injection| match injection {
+        Injection::Required(ty, key) => {
+            Some(quote!(container.depends_on::<Self, #ty>(::testing::Key::<Self>::of(), #key);))
+        }
+        _ => None,
+    });
+
+    // a service is shared between threads and outlives every borrow
+    if let Some(lifetime) = input.generics.lifetimes().next() {
+        return Err(syn::Error::new(lifetime.span(), "Injectable cannot be derived for structs with lifetime parameters"));
+    }
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for param in input.generics.type_params() {
+        let ident = &param.ident;
+        where_clause.predicates.push(parse_quote!(#ident: ::std::marker::Send + ::std::marker::Sync + 'static));
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::testing::Injectable for #name #ty_generics #where_clause {
+            fn inject(
+                container: &mut ::testing::Container,
+            ) -> ::std::result::Result<Self, ::testing::ContainerError> {
+                ::std::result::Result::Ok(#construct)
+            }
+
+            fn declare_dependencies(container: &mut ::testing::Container) {
+                #(#dependencies)*
+            }
+        }
+    })
+}
+
+fn injection(field: &Field) -> syn::Result<Injection> {
+    let mut name = None;
+    let mut default = false;
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("inject")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                name = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            } else if meta.path.is_ident("default") {
+                default = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `name = \"...\"` or `default`"))
+            }
+        })?;
+    }
+    if default {
+        return match name {
+            Some(name) => Err(syn::Error::new(name.span(), "a default field is not resolved, it cannot be named")),
+            None => Ok(Injection::Default),
+        };
+    }
+    let key = |ty: &Type| match &name {
+        Some(name) => quote!(#name),
+        None => quote!(::testing::Key::<#ty>::of()),
+    };
+    if let Some(ty) = generic_argument(&field.ty, "Option").and_then(|ty| generic_argument(ty, "Arc")) {
+        return Ok(Injection::Optional(ty.clone(), key(ty)));
+    }
+    match generic_argument(&field.ty, "Arc") {
+        Some(ty) => Ok(Injection::Required(ty.clone(), key(ty))),
+        None => Err(syn::Error::new(
+            field.ty.span(),
+            "injected fields must be Arc<T> or Option<Arc<T>>, use #[inject(default)] for other fields",
+        )),
+    }
+}
+
+// the T of wrapper<T>, matched on the last path segment so that std::sync::Arc<T> works too
+fn generic_argument<'a>(ty: &'a Type, wrapper: &str) -> Option<&'a Type> {
+    let segment = match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != wrapper {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(arguments) if arguments.args.len() == 1 => match &arguments.args[0] {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,306 @@
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use futures::future::{BoxFuture, FutureExt};
+use futures::lock::Mutex as AsyncMutex;
+
+use crate::error::{cycle_chain, ContainerError};
+use crate::key::{IntoKey, ServiceId};
+use crate::sync_container::SyncContainer;
+
+type AsyncFactory = Arc<
+    dyn for<'a> Fn(AsyncResolver<'a>) -> BoxFuture<'a, Result<Arc<dyn Any + Send + Sync>, ContainerError>>
+        + Send
+        + Sync,
+>;
+
+struct AsyncRegistration {
+    factory: AsyncFactory,
+    type_id: TypeId,
+    type_name: &'static str,
+}
+
+#[derive(Default)]
+pub(crate) struct AsyncState {
+    registrations: HashMap<ServiceId, AsyncRegistration>,
+    // held for the whole construction, concurrent callers await it instead of building again
+    in_flight: Mutex<HashMap<ServiceId, Arc<AsyncMutex<()>>>>,
+    // the chain of every resolver waiting for a gate, together with the service it waits for.
+    // a resolver holds the gates of its whole chain
+    waiting: Mutex<Vec<(Vec<ServiceId>, ServiceId)>>,
+}
+
+impl AsyncState {
+    fn gate(&self, id: &ServiceId) -> Arc<AsyncMutex<()>> {
+        self.in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(id.clone())
+            .or_default()
+            .clone()
+    }
+
+    // follows the resolvers holding id, and the services they wait for, back to this chain. a task
+    // reaching its own chain would wait on itself, so the wait is refused before it starts
+    fn start_waiting(&self, resolving: &[ServiceId], id: &ServiceId) -> Result<Waiting<'_>, ContainerError> {
+        let mut waiting = self.waiting.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut path = resolving.to_vec();
+        path.push(id.clone());
+        let mut target = id.clone();
+        while let Some((_, next)) = waiting.iter().find(|(chain, _)| chain.contains(&target)) {
+            if resolving.contains(next) {
+                return Err(ContainerError::CircularDependency {
+                    chain: cycle_chain(&path, next),
+                });
+            }
+            // a cycle between other resolvers, they report it themselves
+            if path.contains(next) {
+                break;
+            }
+            path.push(next.clone());
+            target = next.clone();
+        }
+        let entry = (resolving.to_vec(), id.clone());
+        waiting.push(entry.clone());
+        Ok(Waiting { state: self, entry })
+    }
+}
+
+struct Waiting<'a> {
+    state: &'a AsyncState,
+    entry: (Vec<ServiceId>, ServiceId),
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        let mut waiting = self.state.waiting.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(position) = waiting.iter().position(|entry| *entry == self.entry) {
+            waiting.remove(position);
+        }
+    }
+}
+
+fn async_factory<F>(factory: F) -> AsyncFactory
+where
+    F: for<'a> Fn(AsyncResolver<'a>) -> BoxFuture<'a, Result<Arc<dyn Any + Send + Sync>, ContainerError>>
+        + Send
+        + Sync
+        + 'static,
+{
+    Arc::new(factory)
+}
+
+// handed to async factories, it carries the resolution chain across await points
+pub struct AsyncResolver<'a> {
+    container: &'a SyncContainer,
+    resolving: Vec<ServiceId>,
+}
+
+impl<'a> AsyncResolver<'a> {
+    pub fn container(&self) -> &'a SyncContainer {
+        self.container
+    }
+
+    pub async fn resolve<T: 'static + Send + Sync>(&self) -> Result<Arc<T>, ContainerError> {
+        self.resolve_id(ServiceId::of::<T>()).await
+    }
+
+    pub async fn resolve_named<T: 'static + Send + Sync>(&self, key: impl IntoKey<T>) -> Result<Arc<T>, ContainerError> {
+        self.resolve_id(key.service_id()).await
+    }
+
+    async fn resolve_id<T: 'static + Send + Sync>(&self, id: ServiceId) -> Result<Arc<T>, ContainerError> {
+        if self.resolving.contains(&id) {
+            return Err(ContainerError::CircularDependency {
+                chain: cycle_chain(&self.resolving, &id),
+            });
+        }
+        let state = &self.container.async_state;
+        let registration = match state.registrations.get(&id) {
+            Some(registration) => registration,
+            None => return self.container.resolve_id(id),
+        };
+        if registration.type_id != TypeId::of::<T>() {
+            return Err(ContainerError::TypeMismatch {
+                name: id.to_string(),
+                stored: registration.type_name,
+                requested: type_name::<T>(),
+            });
+        }
+        if let Ok(v) = self.container.try_get::<T>(id.clone()) {
+            return Ok(v);
+        }
+        let gate = state.gate(&id);
+        let waiting = state.start_waiting(&self.resolving, &id)?;
+        let _building = gate.lock().await;
+        drop(waiting);
+        if let Ok(v) = self.container.try_get::<T>(id.clone()) {
+            return Ok(v);
+        }
+        let mut resolving = self.resolving.clone();
+        resolving.push(id.clone());
+        let resolver = AsyncResolver {
+            container: self.container,
+            resolving,
+        };
+        let v = (registration.factory)(resolver).await?.downcast::<T>().unwrap();
+        self.container.set(id, Arc::clone(&v));
+        Ok(v)
+    }
+}
+
+impl SyncContainer {
+    pub fn register_async<T, F>(&mut self, key: impl IntoKey<T>, factory: F)
+    where
+        T: 'static + Send + Sync,
+        F: for<'a> Fn(AsyncResolver<'a>) -> BoxFuture<'a, Result<Arc<T>, ContainerError>> + Send + Sync + 'static,
+    {
+        let factory = async_factory(move |resolver| {
+            factory(resolver)
+                .map(|result| result.map(|v| v as Arc<dyn Any + Send + Sync>))
+                .boxed()
+        });
+        self.async_state.registrations.insert(
+            key.service_id(),
+            AsyncRegistration {
+                factory,
+                type_id: TypeId::of::<T>(),
+                type_name: type_name::<T>(),
+            },
+        );
+    }
+
+    pub async fn resolve_async<T: 'static + Send + Sync>(&self) -> Result<Arc<T>, ContainerError> {
+        self.resolver().resolve().await
+    }
+
+    pub async fn resolve_named_async<T: 'static + Send + Sync>(
+        &self,
+        key: impl IntoKey<T>,
+    ) -> Result<Arc<T>, ContainerError> {
+        self.resolver().resolve_named(key).await
+    }
+
+    fn resolver(&self) -> AsyncResolver<'_> {
+        AsyncResolver {
+            container: self,
+            resolving: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Context, Poll};
+    use futures::executor::block_on;
+    use futures::future::join;
+    use uuid::Uuid;
+    use crate::Key;
+    use super::*;
+
+    struct ServiceA {
+        pub uuid: Uuid,
+    }
+
+    struct ServiceB {
+        pub service_a: Arc<ServiceA>,
+    }
+
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    static SERVICE_A_BUILDS: AtomicUsize = AtomicUsize::new(0);
+
+    #[test]
+    fn concurrent_callers_share_the_in_flight_build() {
+        let c = &mut SyncContainer::new();
+        c.register_async(Key::of(), |_resolver: AsyncResolver<'_>| {
+            async {
+                SERVICE_A_BUILDS.fetch_add(1, Ordering::SeqCst);
+                YieldOnce(false).await;
+                Ok(Arc::new(ServiceA{uuid: Uuid::new_v4()}))
+            }.boxed()
+        });
+        c.register_async(Key::of(), |resolver: AsyncResolver<'_>| {
+            async move {
+                Ok(Arc::new(ServiceB{service_a: resolver.resolve().await?}))
+            }.boxed()
+        });
+        let (service_a_instance, service_b_instance) = block_on(join(c.resolve_async::<ServiceA>(), c.resolve_async::<ServiceB>()));
+        assert_eq!(SERVICE_A_BUILDS.load(Ordering::SeqCst), 1);
+        assert!(Arc::ptr_eq(&service_a_instance.unwrap(), &service_b_instance.unwrap().service_a));
+        assert!(c.try_get::<ServiceB>(Key::of()).is_ok());
+    }
+
+    #[test]
+    fn async_circular_dependency_is_detected_across_await_points() {
+        let c = &mut SyncContainer::new();
+        c.register_async("circular_a", |resolver: AsyncResolver<'_>| {
+            async move {
+                YieldOnce(false).await;
+                resolver.resolve_named::<ServiceB>("circular_b").await?;
+                Ok(Arc::new(ServiceA{uuid: Uuid::new_v4()}))
+            }.boxed()
+        });
+        c.register_async("circular_b", |resolver: AsyncResolver<'_>| {
+            async move {
+                Ok(Arc::new(ServiceB{service_a: resolver.resolve_named("circular_a").await?}))
+            }.boxed()
+        });
+        assert_eq!(
+            block_on(c.resolve_named_async::<ServiceA>("circular_a")).err().unwrap().to_string(),
+            "circular reference: circular_a -> circular_b -> circular_a"
+        );
+    }
+
+    #[test]
+    fn cycle_entered_concurrently_from_both_ends_is_detected() {
+        let c = &mut SyncContainer::new();
+        c.register_async(Key::of(), |resolver: AsyncResolver<'_>| {
+            async move {
+                YieldOnce(false).await;
+                resolver.resolve::<ServiceB>().await?;
+                Ok(Arc::new(ServiceA{uuid: Uuid::new_v4()}))
+            }.boxed()
+        });
+        c.register_async(Key::of(), |resolver: AsyncResolver<'_>| {
+            async move {
+                Ok(Arc::new(ServiceB{service_a: resolver.resolve().await?}))
+            }.boxed()
+        });
+        let (service_a_result, service_b_result) = block_on(join(c.resolve_async::<ServiceA>(), c.resolve_async::<ServiceB>()));
+        assert!(matches!(service_a_result, Err(ContainerError::CircularDependency { .. })));
+        assert!(matches!(service_b_result, Err(ContainerError::CircularDependency { .. })));
+    }
+
+    #[test]
+    fn async_factory_depends_on_sync_registration() {
+        let c = &mut SyncContainer::new();
+        c.register(Key::of(), |_container: &SyncContainer| {
+            Ok(Arc::new(ServiceA{uuid: Uuid::new_v4()}))
+        });
+        c.register_async(Key::of(), |resolver: AsyncResolver<'_>| {
+            async move {
+                Ok(Arc::new(ServiceB{service_a: resolver.resolve().await?}))
+            }.boxed()
+        });
+        let service_b_instance = block_on(c.resolve_async::<ServiceB>()).unwrap();
+        assert_eq!(c.resolve::<ServiceA>().unwrap().uuid, service_b_instance.service_a.uuid);
+    }
+}
@@ -1,102 +1,270 @@
-use std::collections::HashMap;
+use std::any::type_name;
+use std::collections::{HashMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::Arc;
 
-trait ContainerTrait {
+use crate::error::{cycle_chain, ContainerError};
+use crate::key::IntoKey;
+
+// implemented by service enums for every service they hold, service_enum! generates it
+pub trait ServiceVariant<T> {
+    fn extract(&self) -> Option<Arc<T>>;
+
+    // the variant that is held, for errors about extracting another one
+    fn variant_name(&self) -> &'static str;
+}
+
+pub trait ContainerTrait {
     type Service;
     fn insert(&mut self, name: &str, value: Option<Arc<Self::Service>>) -> Option<Arc<Self::Service>>;
     fn replace(&mut self, name: &str, value: Option<Arc<Self::Service>>);
+    fn remove(&mut self, name: &str);
     fn get(&self, name: &str) -> Option<Option<Arc<Self::Service>>>;
+    fn resolution_stack(&mut self) -> &mut Vec<String>;
+    // marks a service whose builder panicked, until it is built again
+    fn set_poisoned(&mut self, name: &str, poisoned: bool);
+    fn is_poisoned(&self, name: &str) -> bool;
+
+    // like get, but tells a service that is being built or whose builder panicked from a missing one
+    fn try_get(&mut self, name: &str) -> Result<Arc<Self::Service>, ContainerError> {
+        match self.get(name) {
+            Some(Some(service)) => Ok(service),
+            Some(None) => Err(ContainerError::CircularDependency {
+                chain: cycle_chain(self.resolution_stack(), &name.to_string()),
+            }),
+            None if self.is_poisoned(name) => Err(ContainerError::ConstructionPanicked { name: name.to_string() }),
+            None => Err(ContainerError::MissingService { name: name.to_string() }),
+        }
+    }
+
     fn build(
         &mut self,
-        name: &str,
-        builder: fn(container: &mut Self) -> Self::Service,
+        key: impl IntoKey<Self::Service>,
+        builder: impl FnOnce(&mut Self) -> Self::Service,
     ) -> Arc<Self::Service> {
-        match self.get(name) {
-            Some(a) => match a {
-                Some(i) => i.clone(),
-                None => panic!("circular dependency detected for {}", name),
-            },
-            None => {
-                self.insert(name, None);
-                let v = Arc::new(builder(self));
-                self.insert(name, Some(v)).unwrap()
+        build_service(self, &key.service_id().to_string(), builder)
+    }
+
+    // like build, but returns the service inside the variant and fails when the variant holds another one
+    fn resolve<T>(
+        &mut self,
+        key: impl IntoKey<T>,
+        builder: impl FnOnce(&mut Self) -> Self::Service,
+    ) -> Result<Arc<T>, ContainerError>
+    where
+        Self::Service: ServiceVariant<T>,
+    {
+        let name = key.service_id().to_string();
+        let service = build_service(self, &name, builder);
+        service.extract().ok_or_else(|| ContainerError::TypeMismatch {
+            name,
+            stored: service.variant_name(),
+            requested: type_name::<T>(),
+        })
+    }
+}
+
+// typed keys are stored under their name, or the type name of the service when they have none
+fn build_service<C: ContainerTrait + ?Sized>(
+    container: &mut C,
+    name: &str,
+    builder: impl FnOnce(&mut C) -> C::Service,
+) -> Arc<C::Service> {
+    match container.get(name) {
+        Some(a) => match a {
+            Some(i) => {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(service = name, "cache hit");
+                i.clone()
+            }
+            None => panic!(
+                "circular dependency detected: {}",
+                cycle_chain(container.resolution_stack(), &name.to_string()).join(" -> ")
+            ),
+        },
+        None => {
+            container.insert(name, None);
+            container.resolution_stack().push(name.to_string());
+            #[cfg(feature = "tracing")]
+            let span = tracing::debug_span!("build", service = name, type_name = type_name::<C::Service>()).entered();
+            let result = panic::catch_unwind(AssertUnwindSafe(|| builder(container)));
+            #[cfg(feature = "tracing")]
+            {
+                if result.is_err() {
+                    tracing::error!("builder panicked");
+                }
+                drop(span);
+            }
+            container.resolution_stack().pop();
+            match result {
+                Ok(service) => {
+                    container.set_poisoned(name, false);
+                    container.insert(name, Some(Arc::new(service))).unwrap()
+                }
+                Err(payload) => {
+                    // roll back the placeholder so the next build retries instead of reporting a cycle
+                    container.remove(name);
+                    container.set_poisoned(name, true);
+                    panic::resume_unwind(payload)
+                }
             }
         }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use std::any::Any;
-    use std::ops::Deref;
-    use std::panic::{catch_unwind};
-    use std::str::FromStr;
-    use std::sync::RwLock;
-    use uuid::Uuid;
-    use super::*;
+// stores the services of one enum, a placeholder of None marks a service that is being built
+pub struct EnumContainer<S> {
+    storage: HashMap<String, Option<Arc<S>>>,
+    resolving: Vec<String>,
+    poisoned: HashSet<String>,
+}
+
+impl<S> EnumContainer<S> {
+    pub fn new() -> EnumContainer<S> {
+        EnumContainer {
+            storage: HashMap::new(),
+            resolving: Vec::new(),
+            poisoned: HashSet::new(),
+        }
+    }
+}
+
+impl<S> Default for EnumContainer<S> {
+    fn default() -> Self {
+        EnumContainer::new()
+    }
+}
 
+impl<S> ContainerTrait for EnumContainer<S> {
+    type Service = S;
 
-    struct ContainerWithEnumDispatch {
-        storage: RwLock<HashMap<String, Option<Arc<ServiceEnum>>>>,
+    // a built service is never overwritten, it is returned instead. use replace to swap it
+    fn insert(&mut self, name: &str, instance: Option<Arc<S>>) -> Option<Arc<S>> {
+        if let Some(Some(existing)) = self.storage.get(name) {
+            return Some(existing.clone());
+        }
+        self.storage.insert(name.to_string(), instance.clone());
+        instance
     }
 
-    impl ContainerWithEnumDispatch {
-        fn new() -> ContainerWithEnumDispatch {
-            ContainerWithEnumDispatch {
-                storage: RwLock::new(HashMap::new()),
-            }
+    fn replace(&mut self, name: &str, value: Option<Arc<S>>) {
+        #[cfg(feature = "tracing")]
+        if self.storage.contains_key(name) {
+            tracing::debug!(service = name, "instance overridden by replace");
         }
+        self.storage.insert(name.to_string(), value);
     }
 
-    impl ContainerTrait for ContainerWithEnumDispatch {
-        type Service = ServiceEnum;
-
-        fn insert(&mut self, name: &str, instance: Option<Arc<ServiceEnum>>) -> Option<Arc<ServiceEnum>> {
-            let mut lock = self.storage.write().unwrap();
-            let value = lock.get(name);
-            match value {
-                Some(value) => {
-                    match value {
-                        Some(s) => {
-                            Some(s.clone()) // we want to avoid unintentional overwriting of the same service
-                        }
-                        None => {
-                            match instance {
-                                Some(a) => {
-                                    lock.insert(name.to_string(), Some(a.clone()));
-                                    Some(a)
-                                }
-                                None => None
-                            }
-                        }
+    fn remove(&mut self, name: &str) {
+        self.storage.remove(name);
+    }
+
+    fn get(&self, name: &str) -> Option<Option<Arc<S>>> {
+        self.storage.get(name).cloned()
+    }
+
+    fn resolution_stack(&mut self) -> &mut Vec<String> {
+        &mut self.resolving
+    }
+
+    fn set_poisoned(&mut self, name: &str, poisoned: bool) {
+        if poisoned {
+            self.poisoned.insert(name.to_string());
+        } else {
+            self.poisoned.remove(name);
+        }
+    }
+
+    fn is_poisoned(&self, name: &str) -> bool {
+        self.poisoned.contains(name)
+    }
+}
+
+// generates a service enum holding each service behind an Arc, with From, TryFrom and ServiceVariant
+// implementations, an accessor per variant and variant_name. every service type may appear only once,
+// conversions are picked by type
+//
+// service_enum! {
+//     pub enum ServiceEnum {
+//         ServiceA(ServiceA) => service_a,
+//         Logger(Box<dyn Logger>) => logger,
+//     }
+// }
+#[macro_export]
+macro_rules! service_enum {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $($variant:ident($service:ty) => $accessor:ident),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $($variant(::std::sync::Arc<$service>)),*
+        }
+
+        impl $name {
+            $vis fn variant_name(&self) -> &'static str {
+                match self {
+                    $($name::$variant(_) => ::std::concat!(::std::stringify!($name), "::", ::std::stringify!($variant))),*
+                }
+            }
+
+            $(
+                $vis fn $accessor(&self) -> ::std::option::Option<&::std::sync::Arc<$service>> {
+                    match self {
+                        $name::$variant(service) => ::std::option::Option::Some(service),
+                        #[allow(unreachable_patterns)]
+                        _ => ::std::option::Option::None,
                     }
                 }
-                None => {
-                    match instance {
-                        Some(s) => {
-                            lock.insert(name.to_string(), Some(s.clone()));
-                            Some(s)
-                        }
-                        None => {
-                            lock.insert(name.to_string(), None);
-                            None
-                        }
+            )*
+        }
+
+        $(
+            impl $crate::container_enum_dispatch::ServiceVariant<$service> for $name {
+                fn extract(&self) -> ::std::option::Option<::std::sync::Arc<$service>> {
+                    self.$accessor().cloned()
+                }
+
+                fn variant_name(&self) -> &'static str {
+                    $name::variant_name(self)
+                }
+            }
+
+            impl ::std::convert::From<::std::sync::Arc<$service>> for $name {
+                fn from(service: ::std::sync::Arc<$service>) -> $name {
+                    $name::$variant(service)
+                }
+            }
+
+            // a different variant is handed back as the error
+            impl ::std::convert::TryFrom<$name> for ::std::sync::Arc<$service> {
+                type Error = $name;
+
+                fn try_from(service: $name) -> ::std::result::Result<::std::sync::Arc<$service>, $name> {
+                    match service {
+                        $name::$variant(service) => ::std::result::Result::Ok(service),
+                        #[allow(unreachable_patterns)]
+                        other => ::std::result::Result::Err(other),
                     }
                 }
             }
-        }
+        )*
+    };
+}
 
-        fn replace(&mut self, name: &str, value: Option<Arc<Self::Service>>) {
-            self.storage.write().unwrap().insert(name.to_string(), value);
-        }
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::str::FromStr;
+    use uuid::Uuid;
+    use crate::Key;
+    use super::*;
 
-        fn get(&self, key: &str) -> Option<Option<Arc<ServiceEnum>>> {
-            self.storage.read().unwrap().get(key).map(
-                |x| x.as_ref().map(|y| y.clone())
-            )
-        }
-    }
 
+    type ContainerWithEnumDispatch = EnumContainer<ServiceEnum>;
 
     struct ServiceA {
         pub uuid: Uuid,
@@ -145,49 +313,38 @@ mod tests {
         pub service_a: Arc<ServiceAEnum>,
     }
 
+    const SERVICE_A_TRAIT: Key<Box<dyn ServiceATrait>> = Key::new("service_a_trait");
+    const SERVICE_A_ENUM: Key<ServiceAEnum> = Key::new("service_a_enum");
+    const SERVICE_WITH_ENUM_DEPENDENCY_ON_A: Key<ServiceWithEnumDependencyOnA> = Key::new("service_with_enum_dependency_on_a");
+
     fn service_a_with_trait(c: &mut ContainerWithEnumDispatch) -> Arc<Box<dyn ServiceATrait>> {
-        match c.build("service_a_trait", |_container: &mut ContainerWithEnumDispatch| {
+        c.resolve(SERVICE_A_TRAIT, |_container: &mut ContainerWithEnumDispatch| {
             ServiceEnum::ServiceAWithTrait(Arc::new(Box::new(ServiceA{uuid: Uuid::new_v4()}) as Box<dyn ServiceATrait>))
-        }).deref() {
-            ServiceEnum::ServiceAWithTrait(a) => a.clone(),
-            _ => panic!("not a ServiceATrait"),
-        }
+        }).unwrap()
     }
 
     fn service_a_with_enum(c: &mut ContainerWithEnumDispatch) -> Arc<ServiceAEnum> {
-        match c.build("service_a_trait", |_container: &mut ContainerWithEnumDispatch| {
+        c.resolve(SERVICE_A_ENUM, |_container: &mut ContainerWithEnumDispatch| {
             ServiceEnum::ServiceAWithEnum(Arc::new(ServiceAEnum::ServiceA(ServiceA{uuid: Uuid::new_v4()})))
-        }).deref() {
-            ServiceEnum::ServiceAWithEnum(a) => a.clone(),
-            _ => panic!("not a ServiceAEnum"),
-        }
+        }).unwrap()
     }
 
     fn service_with_direct_dependency_on_a(c: &mut ContainerWithEnumDispatch) -> Arc<ServiceWithDirectDependencyOnA> {
-        match c.build("service_with_direct_dependency_on_a", |container: &mut ContainerWithEnumDispatch| {
+        c.resolve("service_with_direct_dependency_on_a", |container: &mut ContainerWithEnumDispatch| {
             ServiceEnum::ServiceWithDirectDependencyOnA(Arc::new(ServiceWithDirectDependencyOnA{service_a: service_a(container)}))
-        }).deref() {
-            ServiceEnum::ServiceWithDirectDependencyOnA(a) => a.clone(),
-            _ => panic!("not a ServiceWithDirectDependencyOnA"),
-        }
+        }).unwrap()
     }
 
     fn service_with_trait_dependency_on_a(c: &mut ContainerWithEnumDispatch) -> Arc<ServiceWithTraitDependencyOnA> {
-        match c.build("service_with_trait_dependency_on_a", |container: &mut ContainerWithEnumDispatch| {
+        c.resolve("service_with_trait_dependency_on_a", |container: &mut ContainerWithEnumDispatch| {
             ServiceEnum::ServiceWithTraitDependencyOnA(Arc::new(ServiceWithTraitDependencyOnA{service_a: service_a_with_trait(container)}))
-        }).deref() {
-            ServiceEnum::ServiceWithTraitDependencyOnA(a) => a.clone(),
-            _ => panic!("not a ServiceWithTraitDependencyOnA"),
-        }
+        }).unwrap()
     }
 
     fn service_with_enum_dependency_on_a(c: &mut ContainerWithEnumDispatch) -> Arc<ServiceWithEnumDependencyOnA> {
-        match c.build("service_with_trait_dependency_on_a", |container: &mut ContainerWithEnumDispatch| {
+        c.resolve(SERVICE_WITH_ENUM_DEPENDENCY_ON_A, |container: &mut ContainerWithEnumDispatch| {
             ServiceEnum::ServiceWithEnumDependencyOnA(Arc::new(ServiceWithEnumDependencyOnA{service_a: service_a_with_enum(container)}))
-        }).deref() {
-            ServiceEnum::ServiceWithEnumDependencyOnA(a) => a.clone(),
-            _ => panic!("not a ServiceWithEnumDependencyOnA"),
-        }
+        }).unwrap()
     }
 
     struct ServiceB {
@@ -204,54 +361,44 @@ mod tests {
         // circular_a: Arc<CircularA>
     }
 
-    enum ServiceEnum {
-        ServiceA(Arc<ServiceA>),
-        ServiceAWithTrait(Arc<Box<dyn ServiceATrait>>),
-        ServiceAWithEnum(Arc<ServiceAEnum>),
-        ServiceB(Arc<ServiceB>),
-        ServiceWithDirectDependencyOnA(Arc<ServiceWithDirectDependencyOnA>),
-        ServiceWithTraitDependencyOnA(Arc<ServiceWithTraitDependencyOnA>),
-        ServiceWithEnumDependencyOnA(Arc<ServiceWithEnumDependencyOnA>),
-        CircularA(Arc<CircularA>),
-        CircularB(Arc<CircularB>),
+    crate::service_enum! {
+        enum ServiceEnum {
+            ServiceA(ServiceA) => service_a,
+            ServiceAWithTrait(Box<dyn ServiceATrait>) => service_a_with_trait,
+            ServiceAWithEnum(ServiceAEnum) => service_a_with_enum,
+            ServiceB(ServiceB) => service_b,
+            ServiceWithDirectDependencyOnA(ServiceWithDirectDependencyOnA) => service_with_direct_dependency_on_a,
+            ServiceWithTraitDependencyOnA(ServiceWithTraitDependencyOnA) => service_with_trait_dependency_on_a,
+            ServiceWithEnumDependencyOnA(ServiceWithEnumDependencyOnA) => service_with_enum_dependency_on_a,
+            CircularA(CircularA) => circular_a,
+            CircularB(CircularB) => circular_b,
+        }
     }
 
     fn service_a(c: &mut ContainerWithEnumDispatch) -> Arc<ServiceA> {
-        match c.build("service_a", |_container: &mut ContainerWithEnumDispatch| {
+        c.resolve("service_a", |_container: &mut ContainerWithEnumDispatch| {
             ServiceEnum::ServiceA(Arc::new(ServiceA{uuid: Uuid::new_v4()}))
-        }).deref() {
-            ServiceEnum::ServiceA(a)=> Arc::clone(&a),
-            _ => panic!("Not a ServiceA"),
-        }
+        }).unwrap()
     }
 
     fn service_b(c: &mut ContainerWithEnumDispatch) -> Arc<ServiceB> {
-        match c.build("service_b", |container: &mut ContainerWithEnumDispatch| -> ServiceEnum {
+        c.resolve("service_b", |container: &mut ContainerWithEnumDispatch| -> ServiceEnum {
             ServiceEnum::ServiceB(Arc::new(ServiceB{service_a: service_a(container)}))
-    }).deref() {
-            ServiceEnum::ServiceB(a) => Arc::clone(&a),
-            _ => panic!("Not a ServiceB"),
-        }
+    }).unwrap()
     }
 
     fn circular_a(c: &mut ContainerWithEnumDispatch) -> Arc<CircularA> {
-        match c.build("circular_a", |container: &mut ContainerWithEnumDispatch| -> ServiceEnum {
+        c.resolve("circular_a", |container: &mut ContainerWithEnumDispatch| -> ServiceEnum {
             circular_b(container);
             ServiceEnum::CircularA(Arc::new(CircularA{}))
-        }).deref() {
-            ServiceEnum::CircularA(a) => Arc::clone(&a),
-            _ => panic!("Not a CircularA"),
-        }
+        }).unwrap()
     }
 
     fn circular_b(c: &mut ContainerWithEnumDispatch) -> Arc<CircularB> {
-        match c.build("circular_b", |container: &mut ContainerWithEnumDispatch| -> ServiceEnum {
+        c.resolve("circular_b", |container: &mut ContainerWithEnumDispatch| -> ServiceEnum {
             circular_a(container);
             ServiceEnum::CircularB(Arc::new(CircularB{}))
-        }).deref() {
-            ServiceEnum::CircularB(a) => Arc::clone(&a),
-            _ => panic!("Not a CircularB"),
-        }
+        }).unwrap()
     }
 
     #[test]
@@ -311,12 +458,90 @@ mod tests {
     fn mock_service_a_with_enum() {
         let c = &mut ContainerWithEnumDispatch::new();
         let service_a_with_trait = service_a_with_trait(c);
-        c.replace("service_a_trait", Some(Arc::new(ServiceEnum::ServiceAWithEnum(Arc::new(ServiceAEnum::ServiceAMock(Box::new(ServiceAMock {}) as Box<dyn ServiceATrait>))))));
+        c.replace("service_a_enum", Some(Arc::new(ServiceEnum::ServiceAWithEnum(Arc::new(ServiceAEnum::ServiceAMock(Box::new(ServiceAMock {}) as Box<dyn ServiceATrait>))))));
         let service_with_trait_dependency_on_a_instance = service_with_enum_dependency_on_a(c);
         assert_eq!(service_with_trait_dependency_on_a_instance.service_a.get_uuid(), ServiceAMock{}.get_uuid());
         assert_ne!(service_a_with_trait.get_uuid(), ServiceAMock{}.get_uuid());
     }
 
+    #[test]
+    fn insert_keeps_the_built_service_and_replace_overwrites_it() {
+        let c = &mut ContainerWithEnumDispatch::new();
+        let service_a_instance = service_a(c);
+        let other = Arc::new(ServiceEnum::ServiceA(Arc::new(ServiceA{uuid: Uuid::new_v4()})));
+        let kept = c.insert("service_a", Some(other.clone())).unwrap();
+        assert_eq!(kept.service_a().unwrap().uuid, service_a_instance.uuid);
+        c.replace("service_a", Some(other.clone()));
+        assert!(Arc::ptr_eq(&c.get("service_a").unwrap().unwrap(), &other));
+    }
+
+    #[test]
+    fn typed_keys_keep_variants_apart() {
+        let c = &mut ContainerWithEnumDispatch::new();
+        let service_a_with_trait_instance = service_a_with_trait(c);
+        let service_a_with_enum_instance = service_a_with_enum(c);
+        assert_ne!(service_a_with_trait_instance.get_uuid(), service_a_with_enum_instance.get_uuid());
+        let service_a_instance = c.resolve(Key::<ServiceA>::of(), |_container: &mut ContainerWithEnumDispatch| {
+            ServiceEnum::ServiceA(Arc::new(ServiceA{uuid: Uuid::new_v4()}))
+        }).unwrap();
+        assert!(Arc::ptr_eq(c.get(std::any::type_name::<ServiceA>()).unwrap().unwrap().service_a().unwrap(), &service_a_instance));
+    }
+
+    #[test]
+    fn resolving_the_wrong_variant_is_an_error() {
+        let c = &mut ContainerWithEnumDispatch::new();
+        service_a(c);
+        let error = c.resolve::<ServiceB>("service_a", |container: &mut ContainerWithEnumDispatch| {
+            ServiceEnum::ServiceB(Arc::new(ServiceB{service_a: service_a(container)}))
+        }).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            format!("key `service_a` holds `ServiceEnum::ServiceA` but `{}` was requested", type_name::<ServiceB>())
+        );
+        assert!(c.resolution_stack().is_empty());
+    }
+
+    #[test]
+    fn service_enum_converts_between_services_and_variants() {
+        let service_a_instance = Arc::new(ServiceA{uuid: Uuid::new_v4()});
+        let service = ServiceEnum::from(service_a_instance.clone());
+        assert!(Arc::ptr_eq(service.service_a().unwrap(), &service_a_instance));
+        assert!(service.service_b().is_none());
+        let service = match Arc::<ServiceB>::try_from(service) {
+            Ok(_) => panic!("a ServiceA was converted to a ServiceB"),
+            Err(service) => service,
+        };
+        assert_eq!(Arc::<ServiceA>::try_from(service).ok().unwrap().uuid, service_a_instance.uuid);
+    }
+
+    #[test]
+    fn builder_captures_runtime_configuration() {
+        let c = &mut ContainerWithEnumDispatch::new();
+        let configured_uuid = Uuid::new_v4();
+        c.build("service_a", move |_container: &mut ContainerWithEnumDispatch| {
+            ServiceEnum::ServiceA(Arc::new(ServiceA{uuid: configured_uuid}))
+        });
+        assert_eq!(service_b(c).service_a.uuid, configured_uuid);
+    }
+
+    #[test]
+    fn panicking_builder_is_rolled_back() {
+        let c = &mut ContainerWithEnumDispatch::new();
+        let payload = catch_unwind(AssertUnwindSafe(|| {
+            c.build("service_b", |container: &mut ContainerWithEnumDispatch| -> ServiceEnum {
+                service_a(container);
+                panic!("connection refused")
+            })
+        }));
+        assert!(payload.is_err());
+        assert!(c.get("service_b").is_none());
+        assert!(matches!(c.try_get("service_b"), Err(ContainerError::ConstructionPanicked { .. })));
+        assert!(matches!(c.try_get("service_c"), Err(ContainerError::MissingService { .. })));
+        assert!(c.resolution_stack().is_empty());
+        assert_eq!(service_b(c).service_a.uuid, service_a(c).uuid);
+        assert!(c.try_get("service_b").is_ok());
+    }
+
     #[test]
     fn circular_dependency_panics() {
          fn get_panic_message(payload: &(dyn Any + Send)) -> Option<&str> {
@@ -336,6 +561,6 @@ mod tests {
             circular_b(c)
         }).unwrap_err();
 
-        assert_eq!(get_panic_message(payload.as_ref()).unwrap(), "circular dependency detected for circular_b");
+        assert_eq!(get_panic_message(payload.as_ref()).unwrap(), "circular dependency detected: circular_b -> circular_a -> circular_b");
     }
 }
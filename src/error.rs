@@ -0,0 +1,88 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum ContainerError {
+    CircularDependency { chain: Vec<String> },
+    TypeMismatch {
+        name: String,
+        stored: &'static str,
+        requested: &'static str,
+    },
+    MissingService { name: String },
+    DuplicateRegistration {
+        name: String,
+        existing: &'static str,
+        registered: &'static str,
+    },
+    MissingDependency { dependent: String, dependency: String },
+    NoActiveScope { name: String },
+    ScopedInSingleton { name: String },
+    ConstructionPanicked { name: String },
+    BuilderPanicked { name: String, message: String },
+    BuilderFailed {
+        name: String,
+        source: Box<dyn Error + Send + Sync>,
+    },
+    InitializeFailed {
+        name: String,
+        source: Box<dyn Error + Send + Sync>,
+    },
+}
+
+impl ContainerError {
+    pub fn builder_failed(name: &str, source: impl Into<Box<dyn Error + Send + Sync>>) -> ContainerError {
+        ContainerError::BuilderFailed {
+            name: name.to_string(),
+            source: source.into(),
+        }
+    }
+}
+
+pub(crate) fn cycle_chain<K: PartialEq + ToString>(resolving: &[K], key: &K) -> Vec<String> {
+    let start = resolving.iter().position(|k| k == key).unwrap_or(resolving.len());
+    resolving[start..].iter().chain([key]).map(|k| k.to_string()).collect()
+}
+
+impl fmt::Display for ContainerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContainerError::CircularDependency { chain } => write!(f, "circular reference: {}", chain.join(" -> ")),
+            ContainerError::TypeMismatch { name, stored, requested } => {
+                write!(f, "key `{}` holds `{}` but `{}` was requested", name, stored, requested)
+            }
+            ContainerError::MissingService { name } => write!(f, "service {} is not in the container", name),
+            ContainerError::DuplicateRegistration { name, existing, registered } => {
+                write!(f, "key `{}` already has a factory for `{}`, cannot register `{}`", name, existing, registered)
+            }
+            ContainerError::MissingDependency { dependent, dependency } => {
+                write!(f, "service {} depends on {}, which is not in the container", dependent, dependency)
+            }
+            ContainerError::NoActiveScope { name } => write!(f, "service {} is scoped but no scope is active", name),
+            ContainerError::ScopedInSingleton { name } => {
+                write!(f, "service {} is scoped and cannot be resolved while building a singleton", name)
+            }
+            ContainerError::ConstructionPanicked { name } => {
+                write!(f, "a previous construction of service {} panicked", name)
+            }
+            ContainerError::BuilderPanicked { name, message } => {
+                write!(f, "building service {} panicked: {}", name, message)
+            }
+            ContainerError::BuilderFailed { name, source } => write!(f, "building service {} failed: {}", name, source),
+            ContainerError::InitializeFailed { name, source } => {
+                write!(f, "initializing service {} failed: {}", name, source)
+            }
+        }
+    }
+}
+
+impl Error for ContainerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ContainerError::BuilderFailed { source, .. } | ContainerError::InitializeFailed { source, .. } => {
+                Some(source.as_ref())
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::key::ServiceId;
+use crate::{Container, Lifetime, Slot};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServiceNode {
+    pub name: String,
+    // unknown for a service that was requested but never registered or built
+    pub type_name: Option<&'static str>,
+    // none for instances passed to set or built through build
+    pub lifetime: Option<Lifetime>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DependencyGraph {
+    pub nodes: Vec<ServiceNode>,
+    // from the dependent to its dependency
+    pub edges: Vec<(String, String)>,
+}
+
+impl Lifetime {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Lifetime::Singleton => "singleton",
+            Lifetime::Scoped => "scoped",
+            Lifetime::Transient => "transient",
+        }
+    }
+}
+
+impl Container {
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        let mut nodes = BTreeMap::new();
+        let mut edges = Vec::new();
+        self.collect_graph(&mut nodes, &mut edges);
+        DependencyGraph {
+            nodes: nodes.into_values().collect(),
+            edges: edges
+                .into_iter()
+                .map(|(dependent, dependency)| (dependent.to_string(), dependency.to_string()))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn record_dependency(&mut self, id: &ServiceId) {
+        if let Some(dependent) = self.resolving.last() {
+            let edge = (dependent.clone(), id.clone());
+            if !self.dependencies.contains(&edge) {
+                self.dependencies.push(edge);
+            }
+        }
+    }
+
+    // child entries win over the parent's, like they do for lookups
+    fn collect_graph(&self, nodes: &mut BTreeMap<ServiceId, ServiceNode>, edges: &mut Vec<(ServiceId, ServiceId)>) {
+        if let Some(parent) = &self.parent {
+            parent.collect_graph(nodes, edges);
+        }
+        for (id, registration) in &self.registrations {
+            let node = node(nodes, id);
+            node.type_name = Some(registration.type_name);
+            node.lifetime = Some(registration.lifetime);
+        }
+        for (id, slot) in self.storage.iter().flatten() {
+            if let Slot::Ready(entry) = slot {
+                node(nodes, id).type_name = Some(entry.type_name);
+            }
+        }
+        for (dependent, dependency) in &self.dependencies {
+            node(nodes, dependent);
+            node(nodes, dependency);
+            let edge = (dependent.clone(), dependency.clone());
+            if !edges.contains(&edge) {
+                edges.push(edge);
+            }
+        }
+    }
+}
+
+impl DependencyGraph {
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph dependencies {\n");
+        for node in &self.nodes {
+            writeln!(dot, "    {} [label={}];", quote(&node.name), quote(&label(node, "\n"))).unwrap();
+        }
+        for (dependent, dependency) in &self.edges {
+            writeln!(dot, "    {} -> {};", quote(dependent), quote(dependency)).unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    // an edge to a service missing from nodes gets a node holding only its name
+    pub fn to_mermaid(&self) -> String {
+        let mut ids: BTreeMap<&str, usize> = self.nodes.iter().enumerate().map(|(i, node)| (node.name.as_str(), i)).collect();
+        let mut mermaid = String::from("graph TD\n");
+        for (i, node) in self.nodes.iter().enumerate() {
+            writeln!(mermaid, "    n{}[\"{}\"]", i, label(node, "<br/>").replace('"', "#quot;")).unwrap();
+        }
+        let mut next = self.nodes.len();
+        for (dependent, dependency) in &self.edges {
+            let [dependent, dependency] = [dependent, dependency].map(|name| {
+                *ids.entry(name.as_str()).or_insert_with(|| {
+                    writeln!(mermaid, "    n{}[\"{}\"]", next, name.replace('"', "#quot;")).unwrap();
+                    next += 1;
+                    next - 1
+                })
+            });
+            writeln!(mermaid, "    n{} --> n{}", dependent, dependency).unwrap();
+        }
+        mermaid
+    }
+
+    pub fn to_json(&self) -> String {
+        let nodes: Vec<String> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                format!(
+                    "{{\"name\":{},\"type\":{},\"lifetime\":{}}}",
+                    quote(&node.name),
+                    node.type_name.map_or("null".to_string(), quote),
+                    node.lifetime.map_or("null".to_string(), |lifetime| quote(lifetime.as_str())),
+                )
+            })
+            .collect();
+        let edges: Vec<String> = self
+            .edges
+            .iter()
+            .map(|(dependent, dependency)| format!("{{\"from\":{},\"to\":{}}}", quote(dependent), quote(dependency)))
+            .collect();
+        format!("{{\"nodes\":[{}],\"edges\":[{}]}}", nodes.join(","), edges.join(","))
+    }
+}
+
+fn node<'a>(nodes: &'a mut BTreeMap<ServiceId, ServiceNode>, id: &ServiceId) -> &'a mut ServiceNode {
+    nodes.entry(id.clone()).or_insert_with(|| ServiceNode {
+        name: id.to_string(),
+        type_name: None,
+        lifetime: None,
+    })
+}
+
+fn label(node: &ServiceNode, separator: &str) -> String {
+    let mut label = node.name.clone();
+    if let Some(type_name) = node.type_name.filter(|type_name| *type_name != node.name) {
+        label.push_str(separator);
+        label.push_str(type_name);
+    }
+    if let Some(lifetime) = node.lifetime {
+        write!(label, "{}({})", separator, lifetime.as_str()).unwrap();
+    }
+    label
+}
+
+// a double-quoted string, escaped for DOT and JSON alike
+fn quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::Key;
+    use super::*;
+
+    struct Config {}
+
+    struct Pool {
+        _config: Arc<Config>,
+    }
+
+    struct Repository {
+        _pool: Arc<Pool>,
+        _config: Arc<Config>,
+    }
+
+    fn wired_container() -> Container {
+        let mut c = Container::new();
+        c.set("config", Arc::new(Config{}));
+        c.register(Key::of(), |container: &mut Container| {
+            Ok(Arc::new(Pool{_config: container.resolve_named("config")?}))
+        });
+        c.register_transient("repository", |container: &mut Container| {
+            Ok(Arc::new(Repository{_pool: container.resolve()?, _config: container.resolve_named("config")?}))
+        });
+        c.resolve_named::<Repository>("repository").unwrap();
+        c
+    }
+
+    #[test]
+    fn graph_records_edges_from_nested_builds() {
+        let graph = wired_container().dependency_graph();
+        let pool = std::any::type_name::<Pool>();
+        assert_eq!(
+            graph.nodes,
+            [
+                ServiceNode{name: "config".to_string(), type_name: Some(std::any::type_name::<Config>()), lifetime: None},
+                ServiceNode{name: "repository".to_string(), type_name: Some(std::any::type_name::<Repository>()), lifetime: Some(Lifetime::Transient)},
+                ServiceNode{name: pool.to_string(), type_name: Some(pool), lifetime: Some(Lifetime::Singleton)},
+            ]
+        );
+        assert_eq!(
+            graph.edges,
+            [
+                ("repository".to_string(), pool.to_string()),
+                (pool.to_string(), "config".to_string()),
+                ("repository".to_string(), "config".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn graph_exports() {
+        let c = &mut Container::new();
+        c.register("pool", |_container: &mut Container| Ok(Arc::new(Config{})));
+        c.register("repository", |container: &mut Container| {
+            container.resolve_named::<Config>("pool")?;
+            Ok(Arc::new(Config{}))
+        });
+        c.resolve_named::<Config>("repository").unwrap();
+        let graph = c.dependency_graph();
+        let config = std::any::type_name::<Config>();
+        assert_eq!(
+            graph.to_dot(),
+            format!(
+                "digraph dependencies {{\n    \"pool\" [label=\"pool\\n{config}\\n(singleton)\"];\n    \
+                 \"repository\" [label=\"repository\\n{config}\\n(singleton)\"];\n    \"repository\" -> \"pool\";\n}}\n"
+            )
+        );
+        assert_eq!(
+            graph.to_mermaid(),
+            format!(
+                "graph TD\n    n0[\"pool<br/>{config}<br/>(singleton)\"]\n    \
+                 n1[\"repository<br/>{config}<br/>(singleton)\"]\n    n1 --> n0\n"
+            )
+        );
+        assert_eq!(
+            graph.to_json(),
+            format!(
+                "{{\"nodes\":[{{\"name\":\"pool\",\"type\":\"{config}\",\"lifetime\":\"singleton\"}},\
+                 {{\"name\":\"repository\",\"type\":\"{config}\",\"lifetime\":\"singleton\"}}],\
+                 \"edges\":[{{\"from\":\"repository\",\"to\":\"pool\"}}]}}"
+            )
+        );
+    }
+
+    #[test]
+    fn mermaid_adds_nodes_missing_from_the_graph() {
+        let graph = DependencyGraph {
+            nodes: vec![ServiceNode{name: "pool".to_string(), type_name: None, lifetime: None}],
+            edges: vec![("repository".to_string(), "pool".to_string())],
+        };
+        assert_eq!(graph.to_mermaid(), "graph TD\n    n0[\"pool\"]\n    n1[\"repository\"]\n    n1 --> n0\n");
+    }
+
+    #[test]
+    fn graph_keeps_a_type_and_a_name_that_read_the_same_apart() {
+        let c = &mut Container::new();
+        c.set(std::any::type_name::<Config>(), Arc::new(Config{}));
+        c.register(Key::of(), |_container: &mut Container| Ok(Arc::new(Config{})));
+        let graph = c.dependency_graph();
+        assert_eq!(graph.nodes.len(), 2);
+        assert_eq!(graph.nodes[0].lifetime, None);
+        assert_eq!(graph.nodes[1].lifetime, Some(Lifetime::Singleton));
+    }
+
+    #[test]
+    fn child_graph_includes_parent() {
+        let root = Arc::new(wired_container());
+        let child = &mut Container::child(&root);
+        child.register("audit", |container: &mut Container| {
+            container.resolve_named::<Repository>("repository")?;
+            Ok(Arc::new(Config{}))
+        });
+        child.resolve_named::<Config>("audit").unwrap();
+        let graph = child.dependency_graph();
+        assert_eq!(graph.nodes.len(), 4);
+        assert!(graph.edges.contains(&("audit".to_string(), "repository".to_string())));
+        assert!(graph.edges.contains(&("repository".to_string(), "config".to_string())));
+    }
+}
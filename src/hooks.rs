@@ -0,0 +1,114 @@
+use std::any::{type_name, Any};
+use std::error::Error;
+use std::sync::Arc;
+
+use crate::error::ContainerError;
+use crate::key::{IntoKey, ServiceId};
+use crate::Container;
+
+pub(crate) type Hook =
+    Arc<dyn Fn(&mut Container, &Arc<dyn Any + Send + Sync>) -> Result<(), Box<dyn Error + Send + Sync>> + Send + Sync>;
+
+pub trait Initialize: Send + Sync {
+    fn initialize(&self, container: &mut Container) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
+
+impl Container {
+    // runs after the factory returns and before the instance is cached or handed out,
+    // an error fails the resolve and nothing is cached
+    pub fn on_built<T: 'static + Send + Sync>(
+        &mut self,
+        key: impl IntoKey<T>,
+        hook: impl Fn(&mut Container, &Arc<T>) -> Result<(), Box<dyn Error + Send + Sync>> + Send + Sync + 'static,
+    ) {
+        let hook: Hook = Arc::new(move |c, instance| match instance.clone().downcast::<T>() {
+            Ok(instance) => hook(c, &instance),
+            Err(_) => Err(format!("the hook expects `{}`", type_name::<T>()).into()),
+        });
+        self.hooks.entry(key.service_id()).or_default().push(hook);
+    }
+
+    pub fn initialize_on_build<T: Initialize + 'static>(&mut self, key: impl IntoKey<T>) {
+        self.on_built(key, |c, instance: &Arc<T>| instance.initialize(c));
+    }
+
+    pub(crate) fn run_hooks(&mut self, id: &ServiceId, instance: Arc<dyn Any + Send + Sync>) -> Result<(), ContainerError> {
+        for hook in self.find_hooks(id) {
+            hook(self, &instance).map_err(|source| ContainerError::InitializeFailed {
+                name: id.to_string(),
+                source,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn find_hooks(&self, id: &ServiceId) -> Vec<Hook> {
+        match self.hooks.get(id) {
+            Some(hooks) => hooks.clone(),
+            None => self.parent.as_ref().map(|parent| parent.find_hooks(id)).unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use crate::Key;
+    use super::*;
+
+    struct Metrics {
+        registered: AtomicBool,
+    }
+
+    struct Worker {
+        metrics: Arc<Metrics>,
+        started: AtomicBool,
+    }
+
+    impl Initialize for Worker {
+        fn initialize(&self, container: &mut Container) -> Result<(), Box<dyn Error + Send + Sync>> {
+            let metrics = container.resolve::<Metrics>()?;
+            if !metrics.registered.load(Ordering::SeqCst) {
+                return Err("metrics are not registered".into());
+            }
+            self.started.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    fn register_services(c: &mut Container) {
+        c.register(Key::of(), |_container: &mut Container| {
+            Ok(Arc::new(Metrics{registered: AtomicBool::new(false)}))
+        });
+        c.register(Key::of(), |container: &mut Container| {
+            Ok(Arc::new(Worker{metrics: container.resolve()?, started: AtomicBool::new(false)}))
+        });
+        c.initialize_on_build(Key::<Worker>::of());
+    }
+
+    #[test]
+    fn hooks_run_before_the_instance_is_handed_out() {
+        let c = &mut Container::new();
+        register_services(c);
+        c.on_built(Key::of(), |_container: &mut Container, metrics: &Arc<Metrics>| {
+            metrics.registered.store(true, Ordering::SeqCst);
+            Ok(())
+        });
+        let worker = c.resolve::<Worker>().unwrap();
+        assert!(worker.started.load(Ordering::SeqCst));
+        assert!(worker.metrics.registered.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn failing_hook_is_reported_and_not_cached() {
+        let c = &mut Container::new();
+        register_services(c);
+        let error = c.resolve::<Worker>().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            format!("initializing service {} failed: metrics are not registered", std::any::type_name::<Worker>())
+        );
+        assert!(matches!(c.try_get::<Worker>(Key::of()), Err(ContainerError::MissingService { .. })));
+        assert!(c.try_get::<Metrics>(Key::of()).is_ok());
+    }
+}
@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use crate::error::ContainerError;
+use crate::key::{IntoKey, Key};
+use crate::{Container, Lifetime};
+
+// usually derived, see testing_derive::Injectable
+pub trait Injectable: Sized + Send + Sync + 'static {
+    fn inject(container: &mut Container) -> Result<Self, ContainerError>;
+
+    // the dependencies inject resolves, declared for validate and build_all
+    fn declare_dependencies(_container: &mut Container) {}
+
+    fn register(container: &mut Container) {
+        Self::register_with_lifetime(container, Lifetime::Singleton);
+    }
+
+    fn register_with_lifetime(container: &mut Container, lifetime: Lifetime) {
+        container.register_with_lifetime(Key::<Self>::of(), lifetime, |c| Ok(Arc::new(Self::inject(c)?)));
+        Self::declare_dependencies(container);
+    }
+}
+
+impl Container {
+    // none when the key itself is not in the container, errors of its dependencies are still returned
+    pub fn resolve_optional<T: 'static + Send + Sync>(
+        &mut self,
+        key: impl IntoKey<T>,
+    ) -> Result<Option<Arc<T>>, ContainerError> {
+        let id = key.service_id();
+        match self.resolve_id(id.clone()) {
+            Ok(v) => Ok(Some(v)),
+            Err(ContainerError::MissingService { name }) if name == id.to_string() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use uuid::Uuid;
+    use crate::Injectable;
+    use super::*;
+
+    #[derive(Injectable)]
+    struct ServiceA {
+        #[inject(default)]
+        pub uuid: Uuid,
+    }
+
+    struct Config {
+        pub retries: u32,
+    }
+
+    #[derive(Injectable)]
+    struct ServiceB {
+        pub service_a: Arc<ServiceA>,
+        #[inject(name = "config")]
+        pub config: Arc<Config>,
+        pub cache: Option<Arc<Uuid>>,
+    }
+
+    #[derive(Injectable)]
+    struct Repository<T> {
+        pub backend: Arc<T>,
+    }
+
+    #[test]
+    fn derived_factory_resolves_fields() {
+        let c = &mut Container::new();
+        ServiceA::register(c);
+        ServiceB::register(c);
+        c.set("config", Arc::new(Config{retries: 3}));
+        let service_b_instance = c.resolve::<ServiceB>().unwrap();
+        assert!(Arc::ptr_eq(&service_b_instance.service_a, &c.resolve::<ServiceA>().unwrap()));
+        assert_eq!(service_b_instance.service_a.uuid, Uuid::nil());
+        assert_eq!(service_b_instance.config.retries, 3);
+        assert!(service_b_instance.cache.is_none());
+    }
+
+    #[test]
+    fn derived_registration_declares_dependencies() {
+        let c = &mut Container::new();
+        ServiceB::register(c);
+        c.set(Key::of(), Arc::new(Uuid::new_v4()));
+        let errors: Vec<String> = c.validate().unwrap_err().iter().map(ToString::to_string).collect();
+        let service_b = std::any::type_name::<ServiceB>();
+        assert_eq!(
+            errors,
+            [
+                format!("service {} depends on {}, which is not in the container", service_b, std::any::type_name::<ServiceA>()),
+                format!("service {} depends on config, which is not in the container", service_b),
+            ]
+        );
+    }
+
+    #[test]
+    fn derived_factory_of_a_generic_struct() {
+        let c = &mut Container::new();
+        ServiceA::register(c);
+        Repository::<ServiceA>::register(c);
+        let repository_instance = c.resolve::<Repository<ServiceA>>().unwrap();
+        assert!(Arc::ptr_eq(&repository_instance.backend, &c.resolve::<ServiceA>().unwrap()));
+    }
+
+    #[test]
+    fn optional_dependency_is_resolved_when_present() {
+        let c = &mut Container::new();
+        ServiceA::register(c);
+        ServiceB::register(c);
+        c.set("config", Arc::new(Config{retries: 3}));
+        let uuid = Uuid::new_v4();
+        c.set(Key::of(), Arc::new(uuid));
+        assert_eq!(c.resolve::<ServiceB>().unwrap().cache.as_deref(), Some(&uuid));
+    }
+}
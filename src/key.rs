@@ -0,0 +1,120 @@
+use std::any::{type_name, TypeId};
+use std::cmp::Ordering;
+use std::fmt;
+use std::marker::PhantomData;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ServiceId {
+    Type { id: TypeId, name: &'static str },
+    Named(String),
+}
+
+impl ServiceId {
+    pub fn of<T: ?Sized + 'static>() -> ServiceId {
+        ServiceId::Type {
+            id: TypeId::of::<T>(),
+            name: type_name::<T>(),
+        }
+    }
+
+    fn rank(&self) -> (&str, Option<TypeId>) {
+        match self {
+            ServiceId::Type { id, name } => (name, Some(*id)),
+            ServiceId::Named(name) => (name, None),
+        }
+    }
+}
+
+// by name, so that sorted output reads alphabetically. a type and a name that read the same stay apart
+impl Ord for ServiceId {
+    fn cmp(&self, other: &ServiceId) -> Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+impl PartialOrd for ServiceId {
+    fn partial_cmp(&self, other: &ServiceId) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for ServiceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceId::Type { name, .. } => f.write_str(name),
+            ServiceId::Named(name) => f.write_str(name),
+        }
+    }
+}
+
+pub struct Key<T: ?Sized> {
+    name: Option<&'static str>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: ?Sized> Key<T> {
+    pub const fn new(name: &'static str) -> Key<T> {
+        Key {
+            name: Some(name),
+            _marker: PhantomData,
+        }
+    }
+
+    // the one unnamed instance of T, looked up by its TypeId
+    pub const fn of() -> Key<T> {
+        Key {
+            name: None,
+            _marker: PhantomData,
+        }
+    }
+
+    pub const fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+}
+
+impl<T: ?Sized> Clone for Key<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for Key<T> {}
+
+impl<T: ?Sized> fmt::Debug for Key<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Key<{}>({:?})", type_name::<T>(), self.name)
+    }
+}
+
+// string keys fit any service type, typed keys only the one they were declared with
+pub trait IntoKey<T: ?Sized> {
+    fn service_id(&self) -> ServiceId;
+}
+
+impl<T: ?Sized + 'static> IntoKey<T> for Key<T> {
+    fn service_id(&self) -> ServiceId {
+        match self.name {
+            Some(name) => ServiceId::Named(name.to_string()),
+            None => ServiceId::of::<T>(),
+        }
+    }
+}
+
+impl<T: ?Sized> IntoKey<T> for &str {
+    fn service_id(&self) -> ServiceId {
+        ServiceId::Named(self.to_string())
+    }
+}
+
+impl<T: ?Sized> IntoKey<T> for String {
+    fn service_id(&self) -> ServiceId {
+        ServiceId::Named(self.clone())
+    }
+}
+
+impl<T: ?Sized> IntoKey<T> for ServiceId {
+    fn service_id(&self) -> ServiceId {
+        self.clone()
+    }
+}
@@ -1,42 +1,521 @@
-mod container_enum_dispatch;
+// lets the paths generated by the derive macro resolve inside this crate too
+extern crate self as testing;
 
-use std::any::Any;
-use std::collections::HashMap;
+#[cfg(feature = "async")]
+mod async_container;
+pub mod container_enum_dispatch;
+mod error;
+mod graph;
+mod hooks;
+mod injectable;
+mod key;
+mod shutdown;
+mod stats;
+mod sync_container;
+mod validate;
+mod warmup;
+
+use std::any::{type_name, Any, TypeId};
+use std::collections::{HashMap, HashSet};
+use std::ops::{Deref, DerefMut};
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(feature = "async")]
+pub use async_container::AsyncResolver;
+pub use error::ContainerError;
+pub use graph::{DependencyGraph, ServiceNode};
+pub use hooks::Initialize;
+pub use injectable::Injectable;
+#[cfg(feature = "derive")]
+pub use testing_derive::Injectable;
+pub use key::{IntoKey, Key, ServiceId};
+#[cfg(feature = "async")]
+pub use shutdown::AsyncDispose;
+pub use shutdown::{Dispose, DisposeOutcome, ShutdownReport};
+pub use stats::{ContainerStats, ServiceStats};
+pub use sync_container::SyncContainer;
+pub use warmup::{WarmedService, WarmupReport};
+
+struct Entry {
+    instance: Arc<dyn Any + Send + Sync>,
+    type_name: &'static str,
+}
+
+impl Entry {
+    fn new<T: 'static + Send + Sync>(instance: Arc<T>) -> Entry {
+        Entry {
+            instance,
+            type_name: type_name::<T>(),
+        }
+    }
+}
 
-struct Container {
-    storage: HashMap<String, Option<Arc<dyn Any + Send + Sync>>>,
+enum Slot {
+    Building,
+    Ready(Entry),
+    // the builder panicked, the next build retries it
+    Poisoned,
+}
+
+type Factory = Arc<dyn Fn(&mut Container) -> Result<Arc<dyn Any + Send + Sync>, ContainerError> + Send + Sync>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lifetime {
+    Singleton,
+    Scoped,
+    Transient,
+}
+
+struct Registration {
+    factory: Factory,
+    // resolves the key as the registered type, for callers that only know the key
+    resolve: fn(&mut Container, ServiceId) -> Result<(), ContainerError>,
+    lifetime: Lifetime,
+    type_id: TypeId,
+    type_name: &'static str,
+}
+
+pub struct Container {
+    parent: Option<Arc<Container>>,
+    registrations: HashMap<ServiceId, Registration>,
+    // the root singletons followed by one layer per active scope
+    storage: Vec<HashMap<ServiceId, Slot>>,
+    // set while a singleton is built, the scope layers are hidden from it meanwhile
+    building_singleton: bool,
+    resolving: Vec<ServiceId>,
+    // every service requested while another one was being built, as (dependent, dependency)
+    dependencies: Vec<(ServiceId, ServiceId)>,
+    check_registrations: bool,
+    hooks: HashMap<ServiceId, Vec<hooks::Hook>>,
+    disposers: HashMap<ServiceId, shutdown::Disposers>,
+    built: Vec<shutdown::Built>,
+    // declared through depends_on, only read by validate
+    declared_dependencies: HashMap<ServiceId, Vec<validate::Dependency>>,
+    stats: HashMap<ServiceId, stats::Counters>,
+    // time spent building dependencies, one entry per builder on the resolution stack
+    nested_build_time: Vec<Duration>,
+    // the innermost service whose builder panicked, read by build_all once the panic reaches it
+    panicked: Option<ServiceId>,
 }
 
 impl Container {
-    fn new() -> Container {
+    pub fn new() -> Container {
+        Container {
+            parent: None,
+            registrations: HashMap::new(),
+            storage: vec![HashMap::new()],
+            building_singleton: false,
+            resolving: Vec::new(),
+            dependencies: Vec::new(),
+            check_registrations: false,
+            hooks: HashMap::new(),
+            disposers: HashMap::new(),
+            built: Vec::new(),
+            declared_dependencies: HashMap::new(),
+            stats: HashMap::new(),
+            nested_build_time: Vec::new(),
+            panicked: None,
+        }
+    }
+
+    // inherits every registration and instance of the parent, while set and newly built services stay local.
+    // a singleton of the parent that is not built yet is built by each child for itself, warm the parent
+    // with build_all first for its children to share it
+    pub fn child(parent: &Arc<Container>) -> Container {
         Container {
-            storage: HashMap::new(),
+            parent: Some(Arc::clone(parent)),
+            ..Container::new()
         }
     }
 
-    fn set(&mut self, name: &str, instance: Arc<dyn Any + Send + Sync>) {
-        self.storage.insert(name.to_string(), Some(instance));
+    // in debug builds, panic when a key is set to an instance of a different type than it holds
+    // or registered a second time
+    pub fn check_registrations(&mut self, enabled: bool) {
+        self.check_registrations = enabled;
     }
 
-    fn build<T: 'static + Send + Sync>(
+    pub fn register<T: 'static + Send + Sync>(
         &mut self,
-        name: &str,
-        builder: fn(container: &mut Container) -> Arc<T>,
-    ) -> Arc<T> {
-        match self.storage.get(name) {
-            Some(a) => match a {
-                Some(i) => i.clone().downcast::<T>().unwrap(),
-                None => panic!("circular reference"),
+        key: impl IntoKey<T>,
+        factory: impl Fn(&mut Container) -> Result<Arc<T>, ContainerError> + Send + Sync + 'static,
+    ) {
+        self.register_with_lifetime(key, Lifetime::Singleton, factory);
+    }
+
+    // resolved once per scope and dropped together with the scope
+    pub fn register_scoped<T: 'static + Send + Sync>(
+        &mut self,
+        key: impl IntoKey<T>,
+        factory: impl Fn(&mut Container) -> Result<Arc<T>, ContainerError> + Send + Sync + 'static,
+    ) {
+        self.register_with_lifetime(key, Lifetime::Scoped, factory);
+    }
+
+    // every resolve runs the factory again, the result is never cached
+    pub fn register_transient<T: 'static + Send + Sync>(
+        &mut self,
+        key: impl IntoKey<T>,
+        factory: impl Fn(&mut Container) -> Result<Arc<T>, ContainerError> + Send + Sync + 'static,
+    ) {
+        self.register_with_lifetime(key, Lifetime::Transient, factory);
+    }
+
+    pub fn register_with_lifetime<T: 'static + Send + Sync>(
+        &mut self,
+        key: impl IntoKey<T>,
+        lifetime: Lifetime,
+        factory: impl Fn(&mut Container) -> Result<Arc<T>, ContainerError> + Send + Sync + 'static,
+    ) {
+        let id = key.service_id();
+        if self.check_registrations {
+            if let Err(e) = self.check_duplicate::<T>(&id) {
+                debug_assert!(false, "{}", e);
+            }
+        }
+        let factory: Factory = Arc::new(move |c| factory(c).map(|v| v as Arc<dyn Any + Send + Sync>));
+        self.registrations.insert(
+            id,
+            Registration {
+                factory,
+                resolve: |c, id| c.resolve_id::<T>(id).map(drop),
+                lifetime,
+                type_id: TypeId::of::<T>(),
+                type_name: type_name::<T>(),
             },
-            None => {
-                self.storage.insert(name.to_string(), None);
-                let v = builder(self);
-                self.storage
-                    .insert(name.to_string(), Some(Arc::clone(&v) as Arc<dyn Any + Send + Sync>));
-                v
+        );
+    }
+
+    pub fn try_register<T: 'static + Send + Sync>(
+        &mut self,
+        key: impl IntoKey<T>,
+        factory: impl Fn(&mut Container) -> Result<Arc<T>, ContainerError> + Send + Sync + 'static,
+    ) -> Result<(), ContainerError> {
+        self.try_register_with_lifetime(key, Lifetime::Singleton, factory)
+    }
+
+    // fails instead of replacing a factory registered earlier under the same key on this container,
+    // registrations of the parent can still be overridden
+    pub fn try_register_with_lifetime<T: 'static + Send + Sync>(
+        &mut self,
+        key: impl IntoKey<T>,
+        lifetime: Lifetime,
+        factory: impl Fn(&mut Container) -> Result<Arc<T>, ContainerError> + Send + Sync + 'static,
+    ) -> Result<(), ContainerError> {
+        let id = key.service_id();
+        self.check_duplicate::<T>(&id)?;
+        self.register_with_lifetime(id, lifetime, factory);
+        Ok(())
+    }
+
+    fn check_duplicate<T: 'static>(&self, id: &ServiceId) -> Result<(), ContainerError> {
+        match self.registrations.get(id) {
+            Some(registration) => Err(ContainerError::DuplicateRegistration {
+                name: id.to_string(),
+                existing: registration.type_name,
+                registered: type_name::<T>(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    // registers a ready instance, it takes precedence over a registered factory
+    pub fn set<T: 'static + Send + Sync>(&mut self, key: impl IntoKey<T>, instance: Arc<T>) {
+        let id = key.service_id();
+        #[cfg(feature = "tracing")]
+        if self.storage[0].contains_key(&id) || self.find_registration(&id).is_some() {
+            tracing::debug!(service = %id, "instance overridden by set");
+        }
+        if self.check_registrations {
+            if let Some(Slot::Ready(entry)) = self.storage[0].get(&id) {
+                debug_assert!(
+                    entry.type_name == type_name::<T>(),
+                    "{}",
+                    ContainerError::TypeMismatch {
+                        name: id.to_string(),
+                        stored: entry.type_name,
+                        requested: type_name::<T>(),
+                    }
+                );
             }
         }
+        self.storage[0].insert(id, Slot::Ready(Entry::new(instance)));
+    }
+
+    // panics when no scope is active
+    pub fn set_scoped<T: 'static + Send + Sync>(&mut self, key: impl IntoKey<T>, instance: Arc<T>) {
+        assert!(self.storage.len() > 1, "set_scoped called outside of a scope");
+        let id = key.service_id();
+        #[cfg(feature = "tracing")]
+        if self.storage.last().unwrap().contains_key(&id) || self.find_registration(&id).is_some() {
+            tracing::debug!(service = %id, "instance overridden by set_scoped");
+        }
+        self.storage.last_mut().unwrap().insert(id, Slot::Ready(Entry::new(instance)));
+    }
+
+    pub fn create_scope(&mut self) -> Scope<'_> {
+        self.storage.push(HashMap::new());
+        Scope { container: self }
+    }
+
+    pub fn try_get<T: 'static + Send + Sync>(&self, key: impl IntoKey<T>) -> Result<Arc<T>, ContainerError> {
+        self.get_by_id(&key.service_id())
+    }
+
+    pub fn resolve<T: 'static + Send + Sync>(&mut self) -> Result<Arc<T>, ContainerError> {
+        self.resolve_id(ServiceId::of::<T>())
+    }
+
+    pub fn resolve_named<T: 'static + Send + Sync>(&mut self, key: impl IntoKey<T>) -> Result<Arc<T>, ContainerError> {
+        self.resolve_id(key.service_id())
+    }
+
+    pub fn build<T: 'static + Send + Sync>(
+        &mut self,
+        key: impl IntoKey<T>,
+        builder: impl FnOnce(&mut Container) -> Arc<T>,
+    ) -> Arc<T> {
+        let id = key.service_id();
+        self.record_dependency(&id);
+        self.record_resolution(&id);
+        self.build_with(id, 0, |c| Ok(builder(c)))
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_build<T: 'static + Send + Sync>(
+        &mut self,
+        key: impl IntoKey<T>,
+        builder: impl FnOnce(&mut Container) -> Result<Arc<T>, ContainerError>,
+    ) -> Result<Arc<T>, ContainerError> {
+        let id = key.service_id();
+        self.record_dependency(&id);
+        self.record_resolution(&id);
+        self.build_with(id, 0, builder)
+    }
+
+    fn resolve_id<T: 'static + Send + Sync>(&mut self, id: ServiceId) -> Result<Arc<T>, ContainerError> {
+        self.record_dependency(&id);
+        self.record_resolution(&id);
+        if matches!(self.find_entry(&id), Some(Slot::Building | Slot::Ready(_))) {
+            let found = self.get_by_id(&id);
+            if found.is_ok() {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(service = %id, "cache hit");
+                self.record_hit(&id);
+            }
+            return found;
+        }
+        let registration = match self.find_registration(&id) {
+            Some(registration) => registration,
+            None => return Err(ContainerError::MissingService { name: id.to_string() }),
+        };
+        if registration.type_id != TypeId::of::<T>() {
+            return Err(ContainerError::TypeMismatch {
+                name: id.to_string(),
+                stored: registration.type_name,
+                requested: type_name::<T>(),
+            });
+        }
+        let factory = registration.factory.clone();
+        let build = |c: &mut Container| Ok(factory(c)?.downcast::<T>().unwrap());
+        match registration.lifetime {
+            Lifetime::Singleton => self.build_with(id, 0, build),
+            Lifetime::Scoped if self.building_singleton => Err(ContainerError::ScopedInSingleton { name: id.to_string() }),
+            Lifetime::Scoped if self.storage.len() == 1 => Err(ContainerError::NoActiveScope { name: id.to_string() }),
+            Lifetime::Scoped => self.build_with(id, self.storage.len() - 1, build),
+            Lifetime::Transient => {
+                if self.resolving.contains(&id) {
+                    return Err(ContainerError::CircularDependency {
+                        chain: error::cycle_chain(&self.resolving, &id),
+                    });
+                }
+                self.construct(&id, build).unwrap_or_else(|payload| panic::resume_unwind(payload))
+            }
+        }
+    }
+
+    // an instance of the parent is skipped when it was built with a dependency this container
+    // overrides, the caller then builds it again here
+    fn find_entry(&self, id: &ServiceId) -> Option<&Slot> {
+        self.storage
+            .iter()
+            .rev()
+            .find_map(|layer| layer.get(id))
+            .or_else(|| self.parent.as_ref()?.find_entry(id).filter(|_| !self.overrides_dependency_of(id)))
+    }
+
+    fn overrides_dependency_of(&self, id: &ServiceId) -> bool {
+        let parent = match &self.parent {
+            Some(parent) => parent,
+            None => return false,
+        };
+        let mut pending = vec![id.clone()];
+        let mut seen = HashSet::new();
+        while let Some(dependent) = pending.pop() {
+            for dependency in parent.recorded_dependencies_of(&dependent) {
+                let overridden = self.registrations.contains_key(&dependency)
+                    || self.storage.iter().any(|layer| layer.contains_key(&dependency));
+                if overridden {
+                    return true;
+                }
+                if seen.insert(dependency.clone()) {
+                    pending.push(dependency);
+                }
+            }
+        }
+        false
+    }
+
+    // the edges recorded while this container and its ancestors built the service
+    fn recorded_dependencies_of(&self, id: &ServiceId) -> Vec<ServiceId> {
+        let mut dependencies: Vec<ServiceId> = self
+            .dependencies
+            .iter()
+            .filter(|(dependent, _)| dependent == id)
+            .map(|(_, dependency)| dependency.clone())
+            .collect();
+        if let Some(parent) = &self.parent {
+            dependencies.extend(parent.recorded_dependencies_of(id));
+        }
+        dependencies
+    }
+
+    fn find_registration(&self, id: &ServiceId) -> Option<&Registration> {
+        self.registrations
+            .get(id)
+            .or_else(|| self.parent.as_ref()?.find_registration(id))
+    }
+
+    fn get_by_id<T: 'static + Send + Sync>(&self, id: &ServiceId) -> Result<Arc<T>, ContainerError> {
+        match self.find_entry(id) {
+            Some(Slot::Ready(entry)) => entry.instance.clone().downcast::<T>().map_err(|_| ContainerError::TypeMismatch {
+                name: id.to_string(),
+                stored: entry.type_name,
+                requested: type_name::<T>(),
+            }),
+            Some(Slot::Building) => Err(ContainerError::CircularDependency {
+                chain: error::cycle_chain(&self.resolving, id),
+            }),
+            Some(Slot::Poisoned) => Err(ContainerError::ConstructionPanicked {
+                name: id.to_string(),
+            }),
+            None => Err(ContainerError::MissingService {
+                name: id.to_string(),
+            }),
+        }
+    }
+
+    fn build_with<T: 'static + Send + Sync>(
+        &mut self,
+        id: ServiceId,
+        layer: usize,
+        builder: impl FnOnce(&mut Container) -> Result<Arc<T>, ContainerError>,
+    ) -> Result<Arc<T>, ContainerError> {
+        match self.get_by_id::<T>(&id) {
+            Err(ContainerError::MissingService { .. } | ContainerError::ConstructionPanicked { .. }) => {
+                self.storage[layer].insert(id.clone(), Slot::Building);
+                // a singleton outlives the scope, it must not capture the scope's instances
+                let scopes = if layer == 0 { self.storage.split_off(1) } else { Vec::new() };
+                let building_singleton = self.building_singleton;
+                self.building_singleton |= layer == 0;
+                let result = self.construct(&id, builder);
+                self.building_singleton = building_singleton;
+                self.storage.extend(scopes);
+                match result {
+                    Ok(Ok(v)) => {
+                        if let Some(disposers) = self.find_disposers(&id).filter(|_| layer == 0) {
+                            self.built.push(shutdown::Built {
+                                id: id.clone(),
+                                instance: v.clone(),
+                                type_name: type_name::<T>(),
+                                disposers,
+                            });
+                        }
+                        self.storage[layer].insert(id, Slot::Ready(Entry::new(Arc::clone(&v))));
+                        Ok(v)
+                    }
+                    Ok(Err(e)) => {
+                        self.storage[layer].remove(&id);
+                        Err(e)
+                    }
+                    Err(payload) => {
+                        self.storage[layer].insert(id, Slot::Poisoned);
+                        panic::resume_unwind(payload)
+                    }
+                }
+            }
+            found => {
+                if found.is_ok() {
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(service = %id, "cache hit");
+                    self.record_hit(&id);
+                }
+                found
+            }
+        }
+    }
+
+    // the resolution stack is unwound even when the builder panics
+    fn construct<T: 'static + Send + Sync>(
+        &mut self,
+        id: &ServiceId,
+        builder: impl FnOnce(&mut Container) -> Result<Arc<T>, ContainerError>,
+    ) -> std::thread::Result<Result<Arc<T>, ContainerError>> {
+        self.resolving.push(id.clone());
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!("build", service = %id, type_name = type_name::<T>()).entered();
+        let started = self.start_build();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let v = builder(self)?;
+            self.run_hooks(id, v.clone())?;
+            Ok(v)
+        }));
+        self.finish_build(id, started);
+        if result.is_err() && self.panicked.is_none() {
+            self.panicked = Some(id.clone());
+        }
+        #[cfg(feature = "tracing")]
+        {
+            match &result {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => tracing::warn!(error = %e, "build failed"),
+                Err(_) => tracing::error!("builder panicked"),
+            }
+            drop(span);
+        }
+        self.resolving.pop();
+        result
+    }
+}
+
+pub struct Scope<'a> {
+    container: &'a mut Container,
+}
+
+impl Deref for Scope<'_> {
+    type Target = Container;
+
+    fn deref(&self) -> &Container {
+        self.container
+    }
+}
+
+impl DerefMut for Scope<'_> {
+    fn deref_mut(&mut self) -> &mut Container {
+        self.container
+    }
+}
+
+impl Drop for Scope<'_> {
+    fn drop(&mut self) {
+        self.container.storage.pop();
+    }
+}
+
+impl Default for Container {
+    fn default() -> Self {
+        Container::new()
     }
 }
 
@@ -193,4 +672,502 @@ mod tests {
         assert_eq!(service_with_trait_dependency_on_a_instance.service_a.get_uuid(), service_a_with_trait_mock.get_uuid());
         assert_ne!(service_a_with_trait.get_uuid(), service_a_with_trait_mock.get_uuid());
     }
+
+    const SERVICE_A: Key<ServiceA> = Key::new("service_a");
+
+    fn typed_service_a(c: &mut Container) -> Arc<ServiceA> {
+        c.build(SERVICE_A, |_container: &mut Container| {
+            Arc::new(ServiceA{uuid: Uuid::new_v4()})
+        })
+    }
+
+    fn circular_a(c: &mut Container) -> Result<Arc<ServiceA>, ContainerError> {
+        c.try_build("circular_a", |container: &mut Container| {
+            circular_b(container)?;
+            Ok(Arc::new(ServiceA{uuid: Uuid::new_v4()}))
+        })
+    }
+
+    fn circular_b(c: &mut Container) -> Result<Arc<ServiceA>, ContainerError> {
+        c.try_build("circular_b", |container: &mut Container| {
+            circular_a(container)?;
+            Ok(Arc::new(ServiceA{uuid: Uuid::new_v4()}))
+        })
+    }
+
+    #[test]
+    fn try_get_missing_service() {
+        let c = &mut Container::new();
+        assert!(matches!(c.try_get::<ServiceA>("service_a"), Err(ContainerError::MissingService { .. })));
+        let service_a_instance = service_a(c);
+        assert_eq!(c.try_get::<ServiceA>("service_a").unwrap().uuid, service_a_instance.uuid);
+    }
+
+    #[test]
+    fn try_build_reports_circular_dependency() {
+        let c = &mut Container::new();
+        match circular_b(c) {
+            Err(ContainerError::CircularDependency { chain }) => assert_eq!(chain, ["circular_b", "circular_a", "circular_b"]),
+            _ => panic!("expected a circular dependency"),
+        }
+        assert_eq!(circular_a(c).err().unwrap().to_string(), "circular reference: circular_a -> circular_b -> circular_a");
+    }
+
+    #[test]
+    fn try_build_reports_type_mismatch() {
+        let c = &mut Container::new();
+        service_with_trait_dependency_on_a(c);
+        let result = c.try_build("service_with_trait_dependency_on_a", |container: &mut Container| {
+            Ok(Arc::new(ServiceWithEnumDependencyOnA{service_a: service_a_with_enum(container)}))
+        });
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "key `service_with_trait_dependency_on_a` holds `testing::tests::ServiceWithTraitDependencyOnA` \
+             but `testing::tests::ServiceWithEnumDependencyOnA` was requested"
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "key `service_a` holds `testing::tests::ServiceA` but `testing::tests::ServiceAEnum` was requested")]
+    fn checked_set_rejects_a_different_type() {
+        let c = &mut Container::new();
+        c.check_registrations(true);
+        service_a(c);
+        c.set("service_a", Arc::new(ServiceAEnum::ServiceA(ServiceA{uuid: Uuid::new_v4()})));
+    }
+
+    #[test]
+    fn failed_builder_can_be_retried() {
+        let c = &mut Container::new();
+        let result = c.try_build::<ServiceA>("service_a", |_container: &mut Container| {
+            Err(ContainerError::builder_failed("service_a", "connection refused"))
+        });
+        assert_eq!(result.err().unwrap().to_string(), "building service service_a failed: connection refused");
+        let service_a_instance = service_a(c);
+        assert_eq!(c.try_get::<ServiceA>("service_a").unwrap().uuid, service_a_instance.uuid);
+    }
+
+    #[test]
+    fn typed_key_shares_storage_with_string_key() {
+        let c = &mut Container::new();
+        let service_a_instance = typed_service_a(c);
+        assert_eq!(c.try_get(SERVICE_A).unwrap().uuid, service_a_instance.uuid);
+        assert_eq!(service_a(c).uuid, service_a_instance.uuid);
+        c.set(SERVICE_A, Arc::new(ServiceA{uuid: Uuid::new_v4()}));
+        assert_ne!(c.try_get(SERVICE_A).unwrap().uuid, service_a_instance.uuid);
+    }
+
+    #[test]
+    fn typed_keys_with_the_same_name_are_duplicate_registrations() {
+        const SERVICE_A_ENUM: Key<ServiceAEnum> = Key::new("service_a");
+        let c = &mut Container::new();
+        c.try_register(SERVICE_A, |_container: &mut Container| Ok(Arc::new(ServiceA{uuid: Uuid::new_v4()}))).unwrap();
+        let result = c.try_register(SERVICE_A_ENUM, |_container: &mut Container| {
+            Ok(Arc::new(ServiceAEnum::ServiceA(ServiceA{uuid: Uuid::new_v4()})))
+        });
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "key `service_a` already has a factory for `testing::tests::ServiceA`, cannot register `testing::tests::ServiceAEnum`"
+        );
+        assert!(c.resolve_named(SERVICE_A).is_ok());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "key `service_a` already has a factory for `testing::tests::ServiceA`")]
+    fn checked_register_rejects_a_duplicate_key() {
+        const SERVICE_A_ENUM: Key<ServiceAEnum> = Key::new("service_a");
+        let c = &mut Container::new();
+        c.check_registrations(true);
+        c.register(SERVICE_A, |_container: &mut Container| Ok(Arc::new(ServiceA{uuid: Uuid::new_v4()})));
+        c.register(SERVICE_A_ENUM, |_container: &mut Container| {
+            Ok(Arc::new(ServiceAEnum::ServiceA(ServiceA{uuid: Uuid::new_v4()})))
+        });
+    }
+
+    #[test]
+    fn resolve_by_type_without_a_name() {
+        let c = &mut Container::new();
+        assert!(matches!(c.resolve::<ServiceA>(), Err(ContainerError::MissingService { .. })));
+        let service_with_direct_dependency_on_a_instance = c.build(Key::of(), |container: &mut Container| {
+            Arc::new(ServiceWithDirectDependencyOnA{service_a: container.build(Key::of(), |_container: &mut Container| {
+                Arc::new(ServiceA{uuid: Uuid::new_v4()})
+            })})
+        });
+        assert_eq!(c.resolve::<ServiceA>().unwrap().uuid, service_with_direct_dependency_on_a_instance.service_a.uuid);
+        assert!(Arc::ptr_eq(&c.resolve::<ServiceWithDirectDependencyOnA>().unwrap(), &service_with_direct_dependency_on_a_instance));
+        let named_service_a_instance = service_a(c);
+        assert_ne!(c.resolve::<ServiceA>().unwrap().uuid, named_service_a_instance.uuid);
+    }
+
+    #[test]
+    fn builder_captures_runtime_configuration() {
+        let c = &mut Container::new();
+        let configured_uuid = Uuid::new_v4();
+        let service_a_instance = c.build("service_a", move |_container: &mut Container| {
+            Arc::new(ServiceA{uuid: configured_uuid})
+        });
+        assert_eq!(service_a_instance.uuid, configured_uuid);
+        assert_eq!(service_with_direct_dependency_on_a(c).service_a.uuid, configured_uuid);
+    }
+
+    fn register_services(c: &mut Container) {
+        c.register(Key::of(), |_container: &mut Container| {
+            Ok(Arc::new(ServiceA{uuid: Uuid::new_v4()}))
+        });
+        c.register(Key::of(), |container: &mut Container| {
+            Ok(Arc::new(ServiceWithDirectDependencyOnA{service_a: container.resolve()?}))
+        });
+        c.register("service_a_enum", |_container: &mut Container| {
+            Ok(Arc::new(ServiceAEnum::ServiceA(ServiceA{uuid: Uuid::new_v4()})))
+        });
+    }
+
+    #[test]
+    fn resolve_builds_registered_factory_once() {
+        let c = &mut Container::new();
+        register_services(c);
+        let service_with_direct_dependency_on_a_instance = c.resolve::<ServiceWithDirectDependencyOnA>().unwrap();
+        let service_a_instance = c.resolve::<ServiceA>().unwrap();
+        assert_eq!(service_with_direct_dependency_on_a_instance.service_a.uuid, service_a_instance.uuid);
+        assert!(Arc::ptr_eq(&c.resolve::<ServiceWithDirectDependencyOnA>().unwrap(), &service_with_direct_dependency_on_a_instance));
+        assert!(c.resolve_named::<ServiceAEnum>("service_a_enum").is_ok());
+    }
+
+    #[test]
+    fn resolve_reports_unregistered_and_mistyped_services() {
+        let c = &mut Container::new();
+        register_services(c);
+        assert!(matches!(c.resolve::<ServiceWithTraitDependencyOnA>(), Err(ContainerError::MissingService { .. })));
+        assert!(matches!(c.resolve_named::<ServiceA>("service_a_enum"), Err(ContainerError::TypeMismatch { .. })));
+    }
+
+    #[test]
+    fn set_instance_takes_precedence_over_registered_factory() {
+        let c = &mut Container::new();
+        register_services(c);
+        let service_a_instance = Arc::new(ServiceA{uuid: Uuid::new_v4()});
+        c.set(Key::of(), service_a_instance.clone());
+        assert_eq!(c.resolve::<ServiceWithDirectDependencyOnA>().unwrap().service_a.uuid, service_a_instance.uuid);
+    }
+
+    #[test]
+    fn transient_is_built_on_every_resolve() {
+        let c = &mut Container::new();
+        register_services(c);
+        c.register_transient(Key::of(), |container: &mut Container| {
+            Ok(Arc::new(ServiceWithDirectDependencyOnA{service_a: container.resolve()?}))
+        });
+        let first = c.resolve::<ServiceWithDirectDependencyOnA>().unwrap();
+        let second = c.resolve::<ServiceWithDirectDependencyOnA>().unwrap();
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert_eq!(first.service_a.uuid, second.service_a.uuid);
+    }
+
+    #[test]
+    fn transient_cycle_is_detected() {
+        let c = &mut Container::new();
+        c.register_transient(Key::of(), |container: &mut Container| {
+            container.resolve::<ServiceWithDirectDependencyOnA>()?;
+            Ok(Arc::new(ServiceA{uuid: Uuid::new_v4()}))
+        });
+        c.register_transient(Key::of(), |container: &mut Container| {
+            Ok(Arc::new(ServiceWithDirectDependencyOnA{service_a: container.resolve()?}))
+        });
+        assert_eq!(
+            c.resolve::<ServiceA>().err().unwrap().to_string(),
+            "circular reference: testing::tests::ServiceA -> testing::tests::ServiceWithDirectDependencyOnA -> testing::tests::ServiceA"
+        );
+    }
+
+    #[test]
+    fn scoped_is_shared_within_a_scope_only() {
+        let c = &mut Container::new();
+        register_services(c);
+        c.register_scoped(Key::of(), |container: &mut Container| {
+            Ok(Arc::new(ServiceWithDirectDependencyOnA{service_a: container.resolve()?}))
+        });
+        assert!(matches!(c.resolve::<ServiceWithDirectDependencyOnA>(), Err(ContainerError::NoActiveScope { .. })));
+        let first = {
+            let scope = &mut c.create_scope();
+            let first = scope.resolve::<ServiceWithDirectDependencyOnA>().unwrap();
+            assert!(Arc::ptr_eq(&first, &scope.resolve::<ServiceWithDirectDependencyOnA>().unwrap()));
+            first
+        };
+        let scope = &mut c.create_scope();
+        let second = scope.resolve::<ServiceWithDirectDependencyOnA>().unwrap();
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert_eq!(first.service_a.uuid, second.service_a.uuid);
+    }
+
+    #[test]
+    fn scoped_instances_are_dropped_with_the_scope() {
+        let c = &mut Container::new();
+        register_services(c);
+        c.register_scoped(Key::of(), |container: &mut Container| {
+            Ok(Arc::new(ServiceWithDirectDependencyOnA{service_a: container.resolve()?}))
+        });
+        let scoped_instance = {
+            let scope = &mut c.create_scope();
+            let request_service_a = Arc::new(ServiceA{uuid: Uuid::new_v4()});
+            scope.set_scoped(Key::of(), request_service_a.clone());
+            let scoped_instance = scope.resolve::<ServiceWithDirectDependencyOnA>().unwrap();
+            assert_eq!(scoped_instance.service_a.uuid, request_service_a.uuid);
+            Arc::downgrade(&scoped_instance)
+        };
+        assert!(scoped_instance.upgrade().is_none());
+        assert!(matches!(c.try_get::<ServiceA>(Key::of()), Err(ContainerError::MissingService { .. })));
+    }
+
+    #[test]
+    fn singleton_built_in_a_scope_does_not_capture_scoped_instances() {
+        let c = &mut Container::new();
+        c.register_scoped(Key::of(), |_container: &mut Container| {
+            Ok(Arc::new(ServiceA{uuid: Uuid::new_v4()}))
+        });
+        c.register(Key::of(), |container: &mut Container| {
+            Ok(Arc::new(ServiceWithDirectDependencyOnA{service_a: container.resolve()?}))
+        });
+        c.register("service_b", |container: &mut Container| {
+            Ok(Arc::new(ServiceWithDirectDependencyOnA{service_a: container.resolve_named("service_a")?}))
+        });
+        c.set("service_a", Arc::new(ServiceA{uuid: Uuid::new_v4()}));
+        let root_service_a_instance = c.try_get::<ServiceA>("service_a").unwrap();
+        {
+            let scope = &mut c.create_scope();
+            assert!(matches!(
+                scope.resolve::<ServiceWithDirectDependencyOnA>(),
+                Err(ContainerError::ScopedInSingleton { .. })
+            ));
+            scope.set_scoped("service_a", Arc::new(ServiceA{uuid: Uuid::new_v4()}));
+            let service_b_instance = scope.resolve_named::<ServiceWithDirectDependencyOnA>("service_b").unwrap();
+            assert_eq!(service_b_instance.service_a.uuid, root_service_a_instance.uuid);
+            assert!(scope.resolve::<ServiceA>().is_ok());
+        }
+        assert!(matches!(c.try_get::<ServiceWithDirectDependencyOnA>(Key::of()), Err(ContainerError::MissingService { .. })));
+        assert!(matches!(c.try_get::<ServiceA>(Key::of()), Err(ContainerError::MissingService { .. })));
+        assert!(matches!(
+            c.resolve::<ServiceWithDirectDependencyOnA>(),
+            Err(ContainerError::ScopedInSingleton { .. })
+        ));
+        let report = c.build_all();
+        assert!(!report.is_ok());
+        assert!(report.failures().all(|(_, e)| matches!(e, ContainerError::ScopedInSingleton { .. })));
+    }
+
+    #[test]
+    fn child_rebuilds_parent_instances_that_depend_on_an_override() {
+        struct ServiceB {
+            service: Arc<ServiceWithDirectDependencyOnA>,
+        }
+
+        let mut root = Container::new();
+        register_services(&mut root);
+        root.register(Key::of(), |container: &mut Container| {
+            Ok(Arc::new(ServiceB{service: container.resolve()?}))
+        });
+        let root_service_b_instance = root.resolve::<ServiceB>().unwrap();
+        let root = Arc::new(root);
+
+        let child = &mut Container::child(&root);
+        let child_service_a_instance = Arc::new(ServiceA{uuid: Uuid::new_v4()});
+        child.set(Key::of(), child_service_a_instance.clone());
+        let service_b_instance = child.resolve::<ServiceB>().unwrap();
+        assert_eq!(service_b_instance.service.service_a.uuid, child_service_a_instance.uuid);
+        assert!(!Arc::ptr_eq(&service_b_instance, &root_service_b_instance));
+        assert!(Arc::ptr_eq(&child.resolve::<ServiceB>().unwrap(), &service_b_instance));
+        assert!(Arc::ptr_eq(&root.try_get::<ServiceB>(Key::of()).unwrap(), &root_service_b_instance));
+
+        let other_child = &mut Container::child(&root);
+        other_child.set("service_a_enum", Arc::new(ServiceAEnum::ServiceA(ServiceA{uuid: Uuid::new_v4()})));
+        assert!(Arc::ptr_eq(&other_child.resolve::<ServiceB>().unwrap(), &root_service_b_instance));
+    }
+
+    #[test]
+    fn child_overrides_without_affecting_parent() {
+        let mut root = Container::new();
+        register_services(&mut root);
+        let root_service_a_instance = root.resolve::<ServiceA>().unwrap();
+        let root = Arc::new(root);
+
+        let child = &mut Container::child(&root);
+        let child_service_a_instance = Arc::new(ServiceA{uuid: Uuid::new_v4()});
+        child.set(Key::of(), child_service_a_instance.clone());
+        let service_with_direct_dependency_on_a_instance = child.resolve::<ServiceWithDirectDependencyOnA>().unwrap();
+        assert_eq!(service_with_direct_dependency_on_a_instance.service_a.uuid, child_service_a_instance.uuid);
+        assert_eq!(root.try_get::<ServiceA>(Key::of()).unwrap().uuid, root_service_a_instance.uuid);
+        assert!(matches!(root.try_get::<ServiceWithDirectDependencyOnA>(Key::of()), Err(ContainerError::MissingService { .. })));
+
+        let other_child = &mut Container::child(&root);
+        assert_eq!(other_child.resolve::<ServiceWithDirectDependencyOnA>().unwrap().service_a.uuid, root_service_a_instance.uuid);
+        let grandchild = &mut Container::child(&Arc::new(Container::child(&root)));
+        assert!(Arc::ptr_eq(&grandchild.resolve::<ServiceA>().unwrap(), &root_service_a_instance));
+    }
+
+    #[test]
+    fn children_share_only_singletons_the_parent_built() {
+        let mut root = Container::new();
+        register_services(&mut root);
+        let root = Arc::new(root);
+        let first = Container::child(&root).resolve::<ServiceA>().unwrap();
+        let second = Container::child(&root).resolve::<ServiceA>().unwrap();
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert!(matches!(root.try_get::<ServiceA>(Key::of()), Err(ContainerError::MissingService { .. })));
+
+        let mut root = Container::new();
+        register_services(&mut root);
+        assert!(root.build_all().is_ok());
+        let root = Arc::new(root);
+        let first = Container::child(&root).resolve::<ServiceA>().unwrap();
+        let second = Container::child(&root).resolve::<ServiceA>().unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(Arc::ptr_eq(&first, &root.try_get::<ServiceA>(Key::of()).unwrap()));
+    }
+
+    #[test]
+    fn panicking_builder_leaves_a_retryable_poisoned_slot() {
+        let c = &mut Container::new();
+        let payload = panic::catch_unwind(AssertUnwindSafe(|| {
+            c.build("service_with_direct_dependency_on_a", |container: &mut Container| -> Arc<ServiceWithDirectDependencyOnA> {
+                service_a(container);
+                panic!("connection refused")
+            })
+        }));
+        assert!(payload.is_err());
+        assert!(matches!(
+            c.try_get::<ServiceWithDirectDependencyOnA>("service_with_direct_dependency_on_a"),
+            Err(ContainerError::ConstructionPanicked { .. })
+        ));
+        let service_with_direct_dependency_on_a_instance = service_with_direct_dependency_on_a(c);
+        assert_eq!(service_with_direct_dependency_on_a_instance.service_a.uuid, service_a(c).uuid);
+    }
+
+    #[test]
+    fn panicking_transient_does_not_report_a_cycle_afterwards() {
+        let c = &mut Container::new();
+        c.register_transient(Key::of(), |_container: &mut Container| -> Result<Arc<ServiceA>, ContainerError> {
+            panic!("connection refused")
+        });
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| c.resolve::<ServiceA>())).is_err());
+        let service_a_instance = service_a(c);
+        assert_eq!(c.try_get::<ServiceA>("service_a").unwrap().uuid, service_a_instance.uuid);
+        assert!(c.resolving.is_empty());
+    }
+
+    #[cfg(feature = "tracing")]
+    #[derive(Default)]
+    struct RecordingSubscriber {
+        spans: std::sync::Mutex<Vec<String>>,
+        log: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    #[cfg(feature = "tracing")]
+    struct FieldsVisitor<'a>(&'a mut Vec<String>);
+
+    #[cfg(feature = "tracing")]
+    impl tracing::field::Visit for FieldsVisitor<'_> {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            match field.name() {
+                "message" => self.0.insert(0, format!("{:?}", value)),
+                "service" | "error" => self.0.push(format!("{:?}", value)),
+                _ => {}
+            }
+        }
+
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            self.record_debug(field, &format_args!("{}", value))
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    impl tracing::Subscriber for RecordingSubscriber {
+        fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut fields = Vec::new();
+            span.record(&mut FieldsVisitor(&mut fields));
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(fields.join(" "));
+            tracing::span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _span: &tracing::span::Id, _values: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            let mut fields = Vec::new();
+            event.record(&mut FieldsVisitor(&mut fields));
+            self.log.lock().unwrap().push(fields.join(" "));
+        }
+
+        fn enter(&self, span: &tracing::span::Id) {
+            let service = self.spans.lock().unwrap()[span.into_u64() as usize - 1].clone();
+            self.log.lock().unwrap().push(format!("enter {}", service));
+        }
+
+        fn exit(&self, span: &tracing::span::Id) {
+            let service = self.spans.lock().unwrap()[span.into_u64() as usize - 1].clone();
+            self.log.lock().unwrap().push(format!("exit {}", service));
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn builds_are_traced_as_nested_spans() {
+        let subscriber = RecordingSubscriber::default();
+        let log = subscriber.log.clone();
+        tracing::subscriber::with_default(subscriber, || {
+            let c = &mut Container::new();
+            service_with_direct_dependency_on_a(c);
+            service_a(c);
+            c.set("service_a", Arc::new(ServiceA{uuid: Uuid::new_v4()}));
+            let _ = c.try_build("failing", |_container: &mut Container| -> Result<Arc<ServiceA>, ContainerError> {
+                Err(ContainerError::builder_failed("failing", "connection refused"))
+            });
+        });
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "enter service_with_direct_dependency_on_a",
+                "enter service_a",
+                "exit service_a",
+                "exit service_with_direct_dependency_on_a",
+                "cache hit service_a",
+                "instance overridden by set service_a",
+                "enter failing",
+                "build failed building service failing failed: connection refused",
+                "exit failing",
+            ]
+        );
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn overriding_a_registration_is_traced() {
+        let subscriber = RecordingSubscriber::default();
+        let log = subscriber.log.clone();
+        tracing::subscriber::with_default(subscriber, || {
+            let c = &mut Container::new();
+            register_services(c);
+            c.set(Key::of(), Arc::new(ServiceA{uuid: Uuid::new_v4()}));
+            c.set("service_b", Arc::new(ServiceA{uuid: Uuid::new_v4()}));
+            let scope = &mut c.create_scope();
+            scope.set_scoped(Key::of(), Arc::new(ServiceA{uuid: Uuid::new_v4()}));
+            scope.set_scoped("service_c", Arc::new(ServiceA{uuid: Uuid::new_v4()}));
+            scope.set_scoped("service_c", Arc::new(ServiceA{uuid: Uuid::new_v4()}));
+        });
+        let service_a = std::any::type_name::<ServiceA>();
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                format!("instance overridden by set {}", service_a),
+                format!("instance overridden by set_scoped {}", service_a),
+                "instance overridden by set_scoped service_c".to_string(),
+            ]
+        );
+    }
 }
@@ -0,0 +1,307 @@
+use std::any::{type_name, Any};
+use std::error::Error;
+use std::sync::Arc;
+#[cfg(feature = "async")]
+use std::time::Duration;
+
+#[cfg(feature = "async")]
+use futures::future::{select, BoxFuture, Either};
+
+use crate::error::ContainerError;
+use crate::key::{IntoKey, ServiceId};
+use crate::Container;
+
+pub trait Dispose: Send + Sync {
+    fn dispose(&self) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
+
+#[cfg(feature = "async")]
+pub trait AsyncDispose: Send + Sync {
+    fn dispose_async(&self) -> BoxFuture<'_, Result<(), Box<dyn Error + Send + Sync>>>;
+}
+
+type Disposer = fn(&Built) -> Result<(), Box<dyn Error + Send + Sync>>;
+
+#[cfg(feature = "async")]
+type AsyncDisposer = for<'a> fn(&'a Built) -> BoxFuture<'a, Result<(), Box<dyn Error + Send + Sync>>>;
+
+// string keys accept a hook for any type, one that does not match the built service fails in the report
+fn dispose_as<T: Dispose + 'static>(built: &Built) -> Result<(), Box<dyn Error + Send + Sync>> {
+    match built.instance.downcast_ref::<T>() {
+        Some(instance) => instance.dispose(),
+        None => Err(built.type_mismatch::<T>().into()),
+    }
+}
+
+#[cfg(feature = "async")]
+fn dispose_async_as<T: AsyncDispose + 'static>(built: &Built) -> BoxFuture<'_, Result<(), Box<dyn Error + Send + Sync>>> {
+    match built.instance.downcast_ref::<T>() {
+        Some(instance) => instance.dispose_async(),
+        None => {
+            let e = built.type_mismatch::<T>();
+            Box::pin(async move { Err(e.into()) })
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Disposers {
+    sync: Option<Disposer>,
+    #[cfg(feature = "async")]
+    asynchronous: Option<AsyncDisposer>,
+}
+
+pub(crate) struct Built {
+    pub(crate) id: ServiceId,
+    pub(crate) instance: Arc<dyn Any + Send + Sync>,
+    pub(crate) type_name: &'static str,
+    pub(crate) disposers: Disposers,
+}
+
+impl Built {
+    fn type_mismatch<T>(&self) -> ContainerError {
+        ContainerError::TypeMismatch {
+            name: self.id.to_string(),
+            stored: self.type_name,
+            requested: type_name::<T>(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DisposeOutcome {
+    Disposed,
+    Failed(Box<dyn Error + Send + Sync>),
+    TimedOut,
+}
+
+#[derive(Debug, Default)]
+pub struct ShutdownReport {
+    // in the order the services were disposed
+    pub services: Vec<(String, DisposeOutcome)>,
+}
+
+impl ShutdownReport {
+    pub fn is_ok(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = (&str, &DisposeOutcome)> {
+        self.services
+            .iter()
+            .filter(|(_, outcome)| !matches!(outcome, DisposeOutcome::Disposed))
+            .map(|(name, outcome)| (name.as_str(), outcome))
+    }
+}
+
+impl Container {
+    // services built under this key are disposed by shutdown, instances passed to set are left alone
+    pub fn dispose_on_shutdown<T: Dispose + 'static>(&mut self, key: impl IntoKey<T>) {
+        self.disposers.entry(key.service_id()).or_default().sync = Some(dispose_as::<T>);
+    }
+
+    // used by shutdown_async, shutdown only runs the sync hook
+    #[cfg(feature = "async")]
+    pub fn dispose_async_on_shutdown<T: AsyncDispose + 'static>(&mut self, key: impl IntoKey<T>) {
+        self.disposers.entry(key.service_id()).or_default().asynchronous = Some(dispose_async_as::<T>);
+    }
+
+    // dependencies finish building before their dependents, so the reverse of the build order
+    // disposes every dependent before the services it uses
+    pub fn shutdown(&mut self) -> ShutdownReport {
+        let mut report = ShutdownReport::default();
+        while let Some(built) = self.built.pop() {
+            let outcome = match built.disposers.sync.map(|dispose| dispose(&built)) {
+                None => continue,
+                Some(Ok(())) => DisposeOutcome::Disposed,
+                Some(Err(e)) => DisposeOutcome::Failed(e),
+            };
+            report.services.push((built.id.to_string(), outcome));
+        }
+        self.storage[0].clear();
+        report
+    }
+
+    // like shutdown, but awaits the async hook where one is registered and gives up on a service
+    // once its hook runs longer than timeout
+    #[cfg(feature = "async")]
+    pub async fn shutdown_async(&mut self, timeout: Duration) -> ShutdownReport {
+        let mut report = ShutdownReport::default();
+        while let Some(built) = self.built.pop() {
+            let outcome = match (built.disposers.asynchronous, built.disposers.sync) {
+                (Some(dispose), _) => match select(dispose(&built), futures_timer::Delay::new(timeout)).await {
+                    Either::Left((Ok(()), _)) => DisposeOutcome::Disposed,
+                    Either::Left((Err(e), _)) => DisposeOutcome::Failed(e),
+                    Either::Right(_) => DisposeOutcome::TimedOut,
+                },
+                (None, Some(dispose)) => match dispose(&built) {
+                    Ok(()) => DisposeOutcome::Disposed,
+                    Err(e) => DisposeOutcome::Failed(e),
+                },
+                (None, None) => continue,
+            };
+            report.services.push((built.id.to_string(), outcome));
+        }
+        self.storage[0].clear();
+        report
+    }
+
+    pub(crate) fn find_disposers(&self, id: &ServiceId) -> Option<Disposers> {
+        self.disposers
+            .get(id)
+            .copied()
+            .or_else(|| self.parent.as_ref()?.find_disposers(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use crate::{ContainerError, Key};
+    use super::*;
+
+    type Log = Arc<Mutex<Vec<&'static str>>>;
+
+    struct Pool {
+        log: Log,
+    }
+
+    struct Repository {
+        _pool: Arc<Pool>,
+        log: Log,
+    }
+
+    struct Writer {
+        _repository: Arc<Repository>,
+        log: Log,
+    }
+
+    impl Dispose for Pool {
+        fn dispose(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+            self.log.lock().unwrap().push("pool");
+            Ok(())
+        }
+    }
+
+    impl Dispose for Repository {
+        fn dispose(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+            self.log.lock().unwrap().push("repository");
+            Err("transaction still open".into())
+        }
+    }
+
+    impl Dispose for Writer {
+        fn dispose(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+            self.log.lock().unwrap().push("writer");
+            Ok(())
+        }
+    }
+
+    fn register_services(c: &mut Container, log: &Log) {
+        let pool_log = log.clone();
+        c.register(Key::of(), move |_container: &mut Container| {
+            Ok(Arc::new(Pool{log: pool_log.clone()}))
+        });
+        let repository_log = log.clone();
+        c.register(Key::of(), move |container: &mut Container| {
+            Ok(Arc::new(Repository{_pool: container.resolve()?, log: repository_log.clone()}))
+        });
+        let writer_log = log.clone();
+        c.register(Key::of(), move |container: &mut Container| {
+            Ok(Arc::new(Writer{_repository: container.resolve()?, log: writer_log.clone()}))
+        });
+        c.dispose_on_shutdown(Key::<Pool>::of());
+        c.dispose_on_shutdown(Key::<Repository>::of());
+        c.dispose_on_shutdown(Key::<Writer>::of());
+    }
+
+    #[test]
+    fn shutdown_disposes_in_reverse_dependency_order() {
+        let log = Log::default();
+        let c = &mut Container::new();
+        register_services(c, &log);
+        c.resolve::<Writer>().unwrap();
+        let report = c.shutdown();
+        assert_eq!(*log.lock().unwrap(), ["writer", "repository", "pool"]);
+        assert!(!report.is_ok());
+        let failures: Vec<_> = report.failures().map(|(name, outcome)| format!("{}: {:?}", name, outcome)).collect();
+        assert_eq!(failures, [format!("{}: Failed(\"transaction still open\")", std::any::type_name::<Repository>())]);
+        assert!(matches!(c.try_get::<Pool>(Key::of()), Err(ContainerError::MissingService { .. })));
+    }
+
+    #[cfg(feature = "async")]
+    struct Queue {
+        _writer: Arc<Writer>,
+        log: Log,
+    }
+
+    #[cfg(feature = "async")]
+    impl AsyncDispose for Queue {
+        fn dispose_async(&self) -> BoxFuture<'_, Result<(), Box<dyn Error + Send + Sync>>> {
+            Box::pin(async {
+                self.log.lock().unwrap().push("queue");
+                futures::future::pending().await
+            })
+        }
+    }
+
+    #[cfg(feature = "async")]
+    impl AsyncDispose for Pool {
+        fn dispose_async(&self) -> BoxFuture<'_, Result<(), Box<dyn Error + Send + Sync>>> {
+            Box::pin(async {
+                self.log.lock().unwrap().push("pool drained");
+                Ok(())
+            })
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_shutdown_times_out_stuck_services() {
+        let log = Log::default();
+        let c = &mut Container::new();
+        register_services(c, &log);
+        let queue_log = log.clone();
+        c.register(Key::of(), move |container: &mut Container| {
+            Ok(Arc::new(Queue{_writer: container.resolve()?, log: queue_log.clone()}))
+        });
+        c.dispose_async_on_shutdown(Key::<Queue>::of());
+        c.dispose_async_on_shutdown(Key::<Pool>::of());
+        c.resolve::<Queue>().unwrap();
+        let report = futures::executor::block_on(c.shutdown_async(Duration::from_millis(20)));
+        assert_eq!(*log.lock().unwrap(), ["queue", "writer", "repository", "pool drained"]);
+        let outcomes: Vec<_> = report.services.iter().map(|(_, outcome)| format!("{:?}", outcome)).collect();
+        assert_eq!(outcomes, ["TimedOut", "Disposed", "Failed(\"transaction still open\")", "Disposed"]);
+    }
+
+    #[test]
+    fn disposer_for_another_type_fails_without_stopping_shutdown() {
+        let log = Log::default();
+        let c = &mut Container::new();
+        register_services(c, &log);
+        c.register("writer", |_container: &mut Container| Ok(Arc::new(Log::default())));
+        c.dispose_on_shutdown::<Writer>("writer");
+        c.resolve::<Repository>().unwrap();
+        c.resolve_named::<Log>("writer").unwrap();
+        let report = c.shutdown();
+        assert_eq!(*log.lock().unwrap(), ["repository", "pool"]);
+        let failures: Vec<_> = report.failures().map(|(name, outcome)| format!("{}: {:?}", name, outcome)).collect();
+        assert_eq!(failures[0], format!(
+            "writer: Failed(TypeMismatch {{ name: \"writer\", stored: \"{}\", requested: \"{}\" }})",
+            std::any::type_name::<Log>(),
+            std::any::type_name::<Writer>()
+        ));
+        assert_eq!(failures.len(), 2);
+    }
+
+    #[test]
+    fn shutdown_skips_services_that_were_set() {
+        let log = Log::default();
+        let c = &mut Container::new();
+        register_services(c, &log);
+        c.set(Key::of(), Arc::new(Pool{log: log.clone()}));
+        c.resolve::<Repository>().unwrap();
+        c.shutdown();
+        assert_eq!(*log.lock().unwrap(), ["repository"]);
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::key::ServiceId;
+use crate::Container;
+
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Counters {
+    resolutions: usize,
+    hits: usize,
+    builds: usize,
+    build_time: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServiceStats {
+    pub name: String,
+    // every resolve, build and try_build of the key
+    pub resolutions: usize,
+    // resolutions answered with an instance that was already built or set
+    pub hits: usize,
+    // times the builder or factory ran, failed runs included
+    pub builds: usize,
+    // spent in the builder itself, without the dependencies it built
+    pub build_time: Duration,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContainerStats {
+    // sorted by name
+    pub services: Vec<ServiceStats>,
+}
+
+impl ContainerStats {
+    pub fn service(&self, name: &str) -> Option<&ServiceStats> {
+        self.services.iter().find(|service| service.name == name)
+    }
+
+    pub fn total_build_time(&self) -> Duration {
+        self.services.iter().map(|service| service.build_time).sum()
+    }
+}
+
+impl Container {
+    // counts only what happened on this container, not on its parent
+    pub fn stats(&self) -> ContainerStats {
+        let services: BTreeMap<&ServiceId, &Counters> = self.stats.iter().collect();
+        ContainerStats {
+            services: services
+                .into_iter()
+                .map(|(id, counters)| ServiceStats {
+                    name: id.to_string(),
+                    resolutions: counters.resolutions,
+                    hits: counters.hits,
+                    builds: counters.builds,
+                    build_time: counters.build_time,
+                })
+                .collect(),
+        }
+    }
+
+    pub(crate) fn record_resolution(&mut self, id: &ServiceId) {
+        self.stats.entry(id.clone()).or_default().resolutions += 1;
+    }
+
+    pub(crate) fn record_hit(&mut self, id: &ServiceId) {
+        self.stats.entry(id.clone()).or_default().hits += 1;
+    }
+
+    pub(crate) fn start_build(&mut self) -> Instant {
+        self.nested_build_time.push(Duration::ZERO);
+        Instant::now()
+    }
+
+    // the whole build counts as nested time of the builder that requested it
+    pub(crate) fn finish_build(&mut self, id: &ServiceId, started: Instant) {
+        let elapsed = started.elapsed();
+        let nested = self.nested_build_time.pop().unwrap_or_default();
+        if let Some(parent) = self.nested_build_time.last_mut() {
+            *parent += elapsed;
+        }
+        let counters = self.stats.entry(id.clone()).or_default();
+        counters.builds += 1;
+        counters.build_time += elapsed.saturating_sub(nested);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread::sleep;
+    use crate::Key;
+    use super::*;
+
+    struct Config {}
+
+    struct Pool {
+        _config: Arc<Config>,
+    }
+
+    #[test]
+    fn stats_count_resolutions_hits_and_builds() {
+        let c = &mut Container::new();
+        c.set("config", Arc::new(Config{}));
+        c.register(Key::of(), |container: &mut Container| {
+            Ok(Arc::new(Pool{_config: container.resolve_named("config")?}))
+        });
+        c.resolve::<Pool>().unwrap();
+        c.resolve::<Pool>().unwrap();
+        c.resolve_named::<Config>("config").unwrap();
+        let stats = c.stats();
+        let pool = std::any::type_name::<Pool>();
+        assert_eq!(stats.services.len(), 2);
+        let config = stats.service("config").unwrap();
+        assert_eq!((config.resolutions, config.hits, config.builds), (2, 2, 0));
+        let pool = stats.service(pool).unwrap();
+        assert_eq!((pool.resolutions, pool.hits, pool.builds), (2, 1, 1));
+    }
+
+    #[test]
+    fn stats_keep_a_type_and_a_name_that_read_the_same_apart() {
+        let c = &mut Container::new();
+        c.set(std::any::type_name::<Config>(), Arc::new(Config{}));
+        c.register(Key::of(), |_container: &mut Container| Ok(Arc::new(Config{})));
+        c.resolve::<Config>().unwrap();
+        c.resolve_named::<Config>(std::any::type_name::<Config>()).unwrap();
+        let stats = c.stats();
+        let counts: Vec<_> = stats.services.iter().map(|service| (service.resolutions, service.hits, service.builds)).collect();
+        assert_eq!(counts, [(1, 1, 0), (1, 0, 1)]);
+    }
+
+    #[test]
+    fn build_time_excludes_nested_builds() {
+        let c = &mut Container::new();
+        c.register("config", |_container: &mut Container| {
+            sleep(Duration::from_millis(50));
+            Ok(Arc::new(Config{}))
+        });
+        c.register(Key::of(), |container: &mut Container| {
+            Ok(Arc::new(Pool{_config: container.resolve_named("config")?}))
+        });
+        c.resolve::<Pool>().unwrap();
+        let stats = c.stats();
+        assert!(stats.service("config").unwrap().build_time >= Duration::from_millis(50));
+        assert!(stats.service(std::any::type_name::<Pool>()).unwrap().build_time < Duration::from_millis(50));
+        assert!(stats.total_build_time() >= Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,430 @@
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock};
+use std::thread::{self, ThreadId};
+
+use crate::error::{cycle_chain, ContainerError};
+use crate::key::{IntoKey, ServiceId};
+use crate::{Entry, Lifetime};
+
+type SyncFactory = Arc<dyn Fn(&SyncContainer) -> Result<Arc<dyn Any + Send + Sync>, ContainerError> + Send + Sync>;
+
+struct SyncRegistration {
+    factory: SyncFactory,
+    lifetime: Lifetime,
+    type_id: TypeId,
+    type_name: &'static str,
+}
+
+// a slot is locked for the whole construction, so other threads wait for the in-flight build
+type Slot = Arc<Mutex<Option<Entry>>>;
+
+pub struct SyncContainer {
+    registrations: HashMap<ServiceId, SyncRegistration>,
+    storage: RwLock<HashMap<ServiceId, Slot>>,
+    resolving: Mutex<HashMap<ThreadId, Vec<ServiceId>>>,
+    // the slot each thread is blocked on, always locked after resolving
+    waiting: Mutex<HashMap<ThreadId, ServiceId>>,
+    #[cfg(feature = "async")]
+    pub(crate) async_state: crate::async_container::AsyncState,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl SyncContainer {
+    pub fn new() -> SyncContainer {
+        SyncContainer {
+            registrations: HashMap::new(),
+            storage: RwLock::new(HashMap::new()),
+            resolving: Mutex::new(HashMap::new()),
+            waiting: Mutex::new(HashMap::new()),
+            #[cfg(feature = "async")]
+            async_state: Default::default(),
+        }
+    }
+
+    pub fn register<T: 'static + Send + Sync>(
+        &mut self,
+        key: impl IntoKey<T>,
+        factory: impl Fn(&SyncContainer) -> Result<Arc<T>, ContainerError> + Send + Sync + 'static,
+    ) {
+        self.register_with_lifetime(key, Lifetime::Singleton, factory);
+    }
+
+    pub fn register_transient<T: 'static + Send + Sync>(
+        &mut self,
+        key: impl IntoKey<T>,
+        factory: impl Fn(&SyncContainer) -> Result<Arc<T>, ContainerError> + Send + Sync + 'static,
+    ) {
+        self.register_with_lifetime(key, Lifetime::Transient, factory);
+    }
+
+    fn register_with_lifetime<T: 'static + Send + Sync>(
+        &mut self,
+        key: impl IntoKey<T>,
+        lifetime: Lifetime,
+        factory: impl Fn(&SyncContainer) -> Result<Arc<T>, ContainerError> + Send + Sync + 'static,
+    ) {
+        let factory: SyncFactory = Arc::new(move |c| factory(c).map(|v| v as Arc<dyn Any + Send + Sync>));
+        self.registrations.insert(
+            key.service_id(),
+            SyncRegistration {
+                factory,
+                lifetime,
+                type_id: TypeId::of::<T>(),
+                type_name: type_name::<T>(),
+            },
+        );
+    }
+
+    // ignored inside the builder of the same key, whose result replaces it anyway. panics when another
+    // thread builds the key while waiting for this one
+    pub fn set<T: 'static + Send + Sync>(&self, key: impl IntoKey<T>, instance: Arc<T>) {
+        let id = key.service_id();
+        if self.check_cycle(&id).is_err() {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(service = %id, "set ignored while the service is built");
+            return;
+        }
+        let slot = self.slot(id.clone());
+        let previous = self.lock_slot(&id, &slot).unwrap_or_else(|e| panic!("{}", e)).replace(Entry::new(instance));
+        #[cfg(feature = "tracing")]
+        if previous.is_some() {
+            tracing::debug!(service = %id, "instance overridden by set");
+        }
+        drop(previous);
+    }
+
+    pub fn try_get<T: 'static + Send + Sync>(&self, key: impl IntoKey<T>) -> Result<Arc<T>, ContainerError> {
+        let id = key.service_id();
+        self.check_cycle(&id)?;
+        let slot = self.storage.read().unwrap_or_else(PoisonError::into_inner).get(&id).cloned();
+        let value = slot.as_ref().map(|slot| self.lock_slot(&id, slot)).transpose()?;
+        match value.as_deref() {
+            Some(Some(entry)) => downcast(&id, entry),
+            Some(None) if slot.as_deref().is_some_and(Mutex::is_poisoned) => {
+                Err(ContainerError::ConstructionPanicked { name: id.to_string() })
+            }
+            _ => Err(ContainerError::MissingService { name: id.to_string() }),
+        }
+    }
+
+    pub fn resolve<T: 'static + Send + Sync>(&self) -> Result<Arc<T>, ContainerError> {
+        self.resolve_id(ServiceId::of::<T>())
+    }
+
+    pub fn resolve_named<T: 'static + Send + Sync>(&self, key: impl IntoKey<T>) -> Result<Arc<T>, ContainerError> {
+        self.resolve_id(key.service_id())
+    }
+
+    pub fn build<T: 'static + Send + Sync>(
+        &self,
+        key: impl IntoKey<T>,
+        builder: impl FnOnce(&SyncContainer) -> Arc<T>,
+    ) -> Arc<T> {
+        self.build_with(key.service_id(), |c| Ok(builder(c)))
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_build<T: 'static + Send + Sync>(
+        &self,
+        key: impl IntoKey<T>,
+        builder: impl FnOnce(&SyncContainer) -> Result<Arc<T>, ContainerError>,
+    ) -> Result<Arc<T>, ContainerError> {
+        self.build_with(key.service_id(), builder)
+    }
+
+    pub(crate) fn resolve_id<T: 'static + Send + Sync>(&self, id: ServiceId) -> Result<Arc<T>, ContainerError> {
+        let registration = match self.registrations.get(&id) {
+            Some(registration) => registration,
+            None => return self.try_get(id),
+        };
+        if registration.type_id != TypeId::of::<T>() {
+            return Err(ContainerError::TypeMismatch {
+                name: id.to_string(),
+                stored: registration.type_name,
+                requested: type_name::<T>(),
+            });
+        }
+        let factory = registration.factory.clone();
+        let build = |c: &SyncContainer| Ok(factory(c)?.downcast::<T>().unwrap());
+        match registration.lifetime {
+            Lifetime::Transient if !self.is_set(&id) => {
+                self.check_cycle(&id)?;
+                self.run_builder(&id, build)
+            }
+            _ => self.build_with(id, build),
+        }
+    }
+
+    fn build_with<T: 'static + Send + Sync>(
+        &self,
+        id: ServiceId,
+        builder: impl FnOnce(&SyncContainer) -> Result<Arc<T>, ContainerError>,
+    ) -> Result<Arc<T>, ContainerError> {
+        self.check_cycle(&id)?;
+        let slot = self.slot(id.clone());
+        let mut value = self.lock_slot(&id, &slot)?;
+        if let Some(entry) = value.as_ref() {
+            #[cfg(feature = "tracing")]
+            tracing::trace!(service = %id, "cache hit");
+            return downcast(&id, entry);
+        }
+        // a panicking builder poisons the slot, which is cleared by the next successful build
+        let v = self.run_builder(&id, builder)?;
+        *value = Some(Entry::new(Arc::clone(&v)));
+        drop(value);
+        slot.clear_poison();
+        Ok(v)
+    }
+
+    fn run_builder<T: 'static + Send + Sync>(
+        &self,
+        id: &ServiceId,
+        builder: impl FnOnce(&SyncContainer) -> Result<Arc<T>, ContainerError>,
+    ) -> Result<Arc<T>, ContainerError> {
+        self.with_resolving(|resolving| resolving.push(id.clone()));
+        let _unwind = ResolvingGuard { container: self };
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("build", service = %id, type_name = type_name::<T>()).entered();
+        let result = builder(self);
+        #[cfg(feature = "tracing")]
+        if let Err(e) = &result {
+            tracing::warn!(error = %e, "build failed");
+        }
+        result
+    }
+
+    // a cycle spanning several threads is caught by lock_slot before it blocks
+    fn check_cycle(&self, id: &ServiceId) -> Result<(), ContainerError> {
+        self.with_resolving(|resolving| {
+            if resolving.contains(id) {
+                Err(ContainerError::CircularDependency {
+                    chain: cycle_chain(resolving, id),
+                })
+            } else {
+                Ok(())
+            }
+        })
+    }
+
+    // follows the thread building id, and the slot it is blocked on, back to this thread. reaching it
+    // would wait on itself, so the wait is refused before it starts
+    fn lock_slot<'s>(&self, id: &ServiceId, slot: &'s Slot) -> Result<MutexGuard<'s, Option<Entry>>, ContainerError> {
+        let resolving = lock(&self.resolving);
+        let mut waiting = lock(&self.waiting);
+        let thread_id = thread::current().id();
+        let own = resolving.get(&thread_id).map(Vec::as_slice).unwrap_or_default();
+        let mut path = own.to_vec();
+        path.push(id.clone());
+        let mut target = id.clone();
+        while let Some(next) = resolving
+            .iter()
+            .find(|(_, stack)| stack.contains(&target))
+            .and_then(|(thread_id, _)| waiting.get(thread_id))
+        {
+            if own.contains(next) {
+                return Err(ContainerError::CircularDependency {
+                    chain: cycle_chain(&path, next),
+                });
+            }
+            // a cycle between other threads, they report it themselves
+            if path.contains(next) {
+                break;
+            }
+            path.push(next.clone());
+            target = next.clone();
+        }
+        waiting.insert(thread_id, id.clone());
+        drop(waiting);
+        drop(resolving);
+        let value = lock(slot);
+        lock(&self.waiting).remove(&thread_id);
+        Ok(value)
+    }
+
+    fn with_resolving<R>(&self, f: impl FnOnce(&mut Vec<ServiceId>) -> R) -> R {
+        let mut resolving = lock(&self.resolving);
+        let thread_id = thread::current().id();
+        let result = f(resolving.entry(thread_id).or_default());
+        if resolving.get(&thread_id).is_some_and(Vec::is_empty) {
+            resolving.remove(&thread_id);
+        }
+        result
+    }
+
+    fn is_set(&self, id: &ServiceId) -> bool {
+        let slot = self.storage.read().unwrap_or_else(PoisonError::into_inner).get(id).cloned();
+        slot.is_some_and(|slot| lock(&slot).is_some())
+    }
+
+    fn slot(&self, id: ServiceId) -> Slot {
+        if let Some(slot) = self.storage.read().unwrap_or_else(PoisonError::into_inner).get(&id) {
+            return slot.clone();
+        }
+        self.storage
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(id)
+            .or_default()
+            .clone()
+    }
+}
+
+struct ResolvingGuard<'a> {
+    container: &'a SyncContainer,
+}
+
+impl Drop for ResolvingGuard<'_> {
+    fn drop(&mut self) {
+        self.container.with_resolving(|resolving| resolving.pop());
+    }
+}
+
+fn downcast<T: 'static + Send + Sync>(id: &ServiceId, entry: &Entry) -> Result<Arc<T>, ContainerError> {
+    entry.instance.clone().downcast::<T>().map_err(|_| ContainerError::TypeMismatch {
+        name: id.to_string(),
+        stored: entry.type_name,
+        requested: type_name::<T>(),
+    })
+}
+
+impl Default for SyncContainer {
+    fn default() -> Self {
+        SyncContainer::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+    use uuid::Uuid;
+    use crate::Key;
+    use super::*;
+
+    struct ServiceA {
+        pub uuid: Uuid,
+    }
+
+    struct ServiceB {
+        pub service_a: Arc<ServiceA>,
+    }
+
+    static SERVICE_A_BUILDS: AtomicUsize = AtomicUsize::new(0);
+
+    fn register_services(c: &mut SyncContainer) {
+        c.register(Key::of(), |_container: &SyncContainer| {
+            SERVICE_A_BUILDS.fetch_add(1, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(20));
+            Ok(Arc::new(ServiceA{uuid: Uuid::new_v4()}))
+        });
+        c.register(Key::of(), |container: &SyncContainer| {
+            Ok(Arc::new(ServiceB{service_a: container.resolve()?}))
+        });
+    }
+
+    #[test]
+    fn singleton_is_built_once_under_contention() {
+        let c = &mut SyncContainer::new();
+        register_services(c);
+        let c = &*c;
+        let instances: Vec<Arc<ServiceB>> = thread::scope(|s| {
+            let handles: Vec<_> = (0..8).map(|_| s.spawn(|| c.resolve::<ServiceB>().unwrap())).collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(SERVICE_A_BUILDS.load(Ordering::SeqCst), 1);
+        assert!(instances.iter().all(|i| Arc::ptr_eq(i, &instances[0])));
+        assert_eq!(c.try_get::<ServiceA>(Key::of()).unwrap().uuid, instances[0].service_a.uuid);
+    }
+
+    #[test]
+    fn circular_dependency_is_detected_per_thread() {
+        let c = &mut SyncContainer::new();
+        c.register("circular_a", |container: &SyncContainer| {
+            container.resolve_named::<ServiceB>("circular_b")?;
+            Ok(Arc::new(ServiceA{uuid: Uuid::new_v4()}))
+        });
+        c.register("circular_b", |container: &SyncContainer| {
+            Ok(Arc::new(ServiceB{service_a: container.resolve_named("circular_a")?}))
+        });
+        assert_eq!(
+            c.resolve_named::<ServiceB>("circular_b").err().unwrap().to_string(),
+            "circular reference: circular_b -> circular_a -> circular_b"
+        );
+        assert!(matches!(c.try_get::<ServiceB>("circular_b"), Err(ContainerError::MissingService { .. })));
+    }
+
+    #[test]
+    fn circular_dependency_across_threads_is_reported() {
+        let c = &mut SyncContainer::new();
+        let started = Arc::new(AtomicUsize::new(0));
+        let wait_for_both = move || {
+            started.fetch_add(1, Ordering::SeqCst);
+            while started.load(Ordering::SeqCst) < 2 {
+                thread::yield_now();
+            }
+        };
+        let wait = wait_for_both.clone();
+        c.register("circular_a", move |container: &SyncContainer| {
+            wait();
+            container.resolve_named::<ServiceB>("circular_b")?;
+            Ok(Arc::new(ServiceA{uuid: Uuid::new_v4()}))
+        });
+        c.register("circular_b", move |container: &SyncContainer| {
+            wait_for_both();
+            Ok(Arc::new(ServiceB{service_a: container.resolve_named("circular_a")?}))
+        });
+        let c = &*c;
+        let (a, b) = thread::scope(|s| {
+            let a = s.spawn(|| c.resolve_named::<ServiceA>("circular_a").err());
+            let b = s.spawn(|| c.resolve_named::<ServiceB>("circular_b").err());
+            (a.join().unwrap(), b.join().unwrap())
+        });
+        assert!(matches!(a, Some(ContainerError::CircularDependency { .. })));
+        assert!(matches!(b, Some(ContainerError::CircularDependency { .. })));
+    }
+
+    #[test]
+    fn set_inside_the_builder_of_the_same_key_is_ignored() {
+        let c = SyncContainer::new();
+        let service_a_instance = c.build("service_a", |container: &SyncContainer| {
+            container.set("service_a", Arc::new(ServiceA{uuid: Uuid::new_v4()}));
+            Arc::new(ServiceA{uuid: Uuid::new_v4()})
+        });
+        assert!(Arc::ptr_eq(&c.try_get::<ServiceA>("service_a").unwrap(), &service_a_instance));
+    }
+
+    #[test]
+    fn panicking_builder_is_reported_and_retried() {
+        let c = SyncContainer::new();
+        let payload = catch_unwind(AssertUnwindSafe(|| {
+            c.build("service_b", |container: &SyncContainer| -> Arc<ServiceB> {
+                container.set("service_a", Arc::new(ServiceA{uuid: Uuid::new_v4()}));
+                panic!("connection refused")
+            })
+        }));
+        assert!(payload.is_err());
+        assert!(matches!(c.try_get::<ServiceB>("service_b"), Err(ContainerError::ConstructionPanicked { .. })));
+        let service_b_instance = c.build("service_b", |container: &SyncContainer| {
+            Arc::new(ServiceB{service_a: container.try_get("service_a").unwrap()})
+        });
+        assert!(Arc::ptr_eq(&c.try_get::<ServiceB>("service_b").unwrap(), &service_b_instance));
+    }
+
+    #[test]
+    fn set_and_build_through_shared_reference() {
+        let c = SyncContainer::new();
+        let service_a_instance = Arc::new(ServiceA{uuid: Uuid::new_v4()});
+        c.set("service_a", service_a_instance.clone());
+        let service_b_instance = c.build("service_b", |container: &SyncContainer| {
+            Arc::new(ServiceB{service_a: container.try_get("service_a").unwrap()})
+        });
+        assert_eq!(service_b_instance.service_a.uuid, service_a_instance.uuid);
+        assert!(matches!(c.try_get::<ServiceA>("service_b"), Err(ContainerError::TypeMismatch { .. })));
+    }
+}
@@ -0,0 +1,195 @@
+use std::any::{type_name, TypeId};
+use std::collections::{BTreeMap, HashSet};
+
+use crate::error::{cycle_chain, ContainerError};
+use crate::key::{IntoKey, ServiceId};
+use crate::{Container, Slot};
+
+#[derive(Clone)]
+pub(crate) struct Dependency {
+    pub(crate) id: ServiceId,
+    type_id: TypeId,
+    type_name: &'static str,
+}
+
+impl Container {
+    // declares that the factory registered under key resolves dependency, for validate to check
+    pub fn depends_on<T: 'static, D: 'static>(&mut self, key: impl IntoKey<T>, dependency: impl IntoKey<D>) {
+        self.declared_dependencies.entry(key.service_id()).or_default().push(Dependency {
+            id: dependency.service_id(),
+            type_id: TypeId::of::<D>(),
+            type_name: type_name::<D>(),
+        });
+    }
+
+    // checks the declared dependencies without building anything: every dependency is registered
+    // or set with the declared type, and no service depends on itself
+    pub fn validate(&self) -> Result<(), Vec<ContainerError>> {
+        let declared = self.all_declared_dependencies();
+        let mut errors = Vec::new();
+        for (dependent, dependencies) in &declared {
+            for dependency in dependencies {
+                if let Err(e) = self.check_dependency(dependent, dependency) {
+                    errors.push(e);
+                }
+            }
+        }
+        let mut visited = HashSet::new();
+        for id in declared.keys() {
+            find_cycles(id, &declared, &mut Vec::new(), &mut visited, &mut errors);
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn check_dependency(&self, dependent: &ServiceId, dependency: &Dependency) -> Result<(), ContainerError> {
+        let (type_id, stored) = match (self.find_entry(&dependency.id), self.find_registration(&dependency.id)) {
+            (Some(Slot::Ready(entry)), _) => (entry.instance.as_ref().type_id(), entry.type_name),
+            (_, Some(registration)) => (registration.type_id, registration.type_name),
+            _ => {
+                return Err(ContainerError::MissingDependency {
+                    dependent: dependent.to_string(),
+                    dependency: dependency.id.to_string(),
+                })
+            }
+        };
+        if type_id != dependency.type_id {
+            return Err(ContainerError::TypeMismatch {
+                name: dependency.id.to_string(),
+                stored,
+                requested: dependency.type_name,
+            });
+        }
+        Ok(())
+    }
+
+    // sorted so that validation reports errors in a stable order
+    fn all_declared_dependencies(&self) -> BTreeMap<ServiceId, Vec<Dependency>> {
+        let mut declared = match &self.parent {
+            Some(parent) => parent.all_declared_dependencies(),
+            None => BTreeMap::new(),
+        };
+        for (id, dependencies) in &self.declared_dependencies {
+            declared.insert(id.clone(), dependencies.clone());
+        }
+        declared
+    }
+}
+
+fn find_cycles(
+    id: &ServiceId,
+    declared: &BTreeMap<ServiceId, Vec<Dependency>>,
+    path: &mut Vec<ServiceId>,
+    visited: &mut HashSet<ServiceId>,
+    errors: &mut Vec<ContainerError>,
+) {
+    if path.contains(id) {
+        errors.push(ContainerError::CircularDependency {
+            chain: cycle_chain(path, id),
+        });
+        return;
+    }
+    if !visited.insert(id.clone()) {
+        return;
+    }
+    path.push(id.clone());
+    if let Some(dependencies) = declared.get(id) {
+        for dependency in dependencies {
+            find_cycles(&dependency.id, declared, path, visited, errors);
+        }
+    }
+    path.pop();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::Key;
+    use super::*;
+
+    struct Config {}
+
+    struct Pool {
+        _config: Arc<Config>,
+    }
+
+    struct Repository {
+        _pool: Arc<Pool>,
+    }
+
+    fn register_services(c: &mut Container) {
+        c.register(Key::of(), |container: &mut Container| {
+            Ok(Arc::new(Pool{_config: container.resolve_named("config")?}))
+        });
+        c.depends_on(Key::<Pool>::of(), Key::<Config>::new("config"));
+        c.register(Key::of(), |container: &mut Container| {
+            Ok(Arc::new(Repository{_pool: container.resolve()?}))
+        });
+        c.depends_on(Key::<Repository>::of(), Key::<Pool>::of());
+    }
+
+    #[test]
+    fn validate_accepts_complete_wiring_without_building() {
+        let c = &mut Container::new();
+        register_services(c);
+        c.set("config", Arc::new(Config{}));
+        assert!(c.validate().is_ok());
+        assert!(matches!(c.try_get::<Pool>(Key::of()), Err(ContainerError::MissingService { .. })));
+    }
+
+    #[test]
+    fn validate_reports_missing_and_mistyped_dependencies() {
+        let c = &mut Container::new();
+        register_services(c);
+        c.depends_on::<Repository, Config>(Key::of(), "config");
+        c.depends_on::<Repository, Pool>(Key::of(), "config");
+        let errors: Vec<String> = c.validate().unwrap_err().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            [
+                format!("service {} depends on config, which is not in the container", type_name::<Pool>()),
+                format!("service {} depends on config, which is not in the container", type_name::<Repository>()),
+                format!("service {} depends on config, which is not in the container", type_name::<Repository>()),
+            ]
+        );
+        c.register("config", |_container: &mut Container| Ok(Arc::new(Config{})));
+        let errors: Vec<String> = c.validate().unwrap_err().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            [format!("key `config` holds `{}` but `{}` was requested", type_name::<Config>(), type_name::<Pool>())]
+        );
+    }
+
+    #[test]
+    fn validate_keeps_a_type_and_a_name_that_read_the_same_apart() {
+        let c = &mut Container::new();
+        register_services(c);
+        c.set("config", Arc::new(Config{}));
+        c.depends_on::<Pool, Config>(type_name::<Pool>(), "cache");
+        let errors: Vec<String> = c.validate().unwrap_err().iter().map(ToString::to_string).collect();
+        assert_eq!(errors, [format!("service {} depends on cache, which is not in the container", type_name::<Pool>())]);
+    }
+
+    #[test]
+    fn validate_reports_cycles() {
+        let c = &mut Container::new();
+        register_services(c);
+        c.register("config", |container: &mut Container| {
+            container.resolve::<Repository>()?;
+            Ok(Arc::new(Config{}))
+        });
+        c.depends_on::<Config, Repository>("config", Key::of());
+        let errors: Vec<String> = c.validate().unwrap_err().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            [format!(
+                "circular reference: config -> {repository} -> {pool} -> config",
+                repository = type_name::<Repository>(),
+                pool = type_name::<Pool>()
+            )]
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use std::any::Any;
+use std::collections::HashSet;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+use crate::error::ContainerError;
+use crate::key::ServiceId;
+use crate::{Container, Lifetime, Slot};
+
+#[derive(Debug)]
+pub struct WarmedService {
+    pub name: String,
+    pub elapsed: Duration,
+    pub result: Result<(), ContainerError>,
+}
+
+#[derive(Debug, Default)]
+pub struct WarmupReport {
+    // in the order the services were built
+    pub services: Vec<WarmedService>,
+}
+
+impl WarmupReport {
+    pub fn is_ok(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = (&str, &ContainerError)> {
+        self.services
+            .iter()
+            .filter_map(|service| Some((service.name.as_str(), service.result.as_ref().err()?)))
+    }
+}
+
+impl Container {
+    // builds every singleton registered on this container, dependencies declared through depends_on
+    // first so that the timing of a service does not include them. a failure does not stop the
+    // warm-up, builds run one after the other since the container is borrowed mutably
+    pub fn build_all(&mut self) -> WarmupReport {
+        let mut ids: Vec<ServiceId> = self
+            .registrations
+            .iter()
+            .filter(|(_, registration)| registration.lifetime == Lifetime::Singleton)
+            .map(|(id, _)| id.clone())
+            .collect();
+        ids.sort_by_cached_key(ToString::to_string);
+        let mut order = Vec::new();
+        let mut visited = HashSet::new();
+        for id in &ids {
+            self.dependencies_first(id, &mut visited, &mut order);
+        }
+
+        let mut report = WarmupReport::default();
+        for id in order {
+            if matches!(self.storage[0].get(&id), Some(Slot::Ready(_))) {
+                continue;
+            }
+            let resolve = self.registrations[&id].resolve;
+            let started = Instant::now();
+            self.panicked = None;
+            let result = panic::catch_unwind(AssertUnwindSafe(|| resolve(self, id.clone()))).unwrap_or_else(|payload| {
+                Err(ContainerError::BuilderPanicked {
+                    name: self.panicked.take().unwrap_or_else(|| id.clone()).to_string(),
+                    message: panic_message(payload.as_ref()),
+                })
+            });
+            report.services.push(WarmedService {
+                name: id.to_string(),
+                elapsed: started.elapsed(),
+                result,
+            });
+        }
+        report
+    }
+
+    // cycles are left for the build to report
+    fn dependencies_first(&self, id: &ServiceId, visited: &mut HashSet<ServiceId>, order: &mut Vec<ServiceId>) {
+        if !visited.insert(id.clone()) {
+            return;
+        }
+        for dependency in self.declared_dependencies.get(id).into_iter().flatten() {
+            self.dependencies_first(&dependency.id, visited, order);
+        }
+        if self.registrations.get(id).is_some_and(|registration| registration.lifetime == Lifetime::Singleton) {
+            order.push(id.clone());
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match payload.downcast_ref::<&'static str>() {
+        Some(message) => message.to_string(),
+        None => match payload.downcast_ref::<String>() {
+            Some(message) => message.clone(),
+            None => "non-string panic payload".to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::Key;
+    use super::*;
+
+    struct Config {}
+
+    struct Pool {
+        _config: Arc<Config>,
+    }
+
+    struct Repository {
+        _pool: Arc<Pool>,
+    }
+
+    struct Cache {}
+
+    #[test]
+    fn build_all_builds_dependencies_first() {
+        let c = &mut Container::new();
+        c.register("repository", |container: &mut Container| {
+            Ok(Arc::new(Repository{_pool: container.resolve_named("pool")?}))
+        });
+        c.depends_on::<Repository, Pool>("repository", "pool");
+        c.register("pool", |container: &mut Container| {
+            Ok(Arc::new(Pool{_config: container.resolve_named("config")?}))
+        });
+        c.depends_on::<Pool, Config>("pool", "config");
+        c.register("config", |_container: &mut Container| Ok(Arc::new(Config{})));
+        c.register_transient("cache", |_container: &mut Container| Ok(Arc::new(Cache{})));
+        let report = c.build_all();
+        assert!(report.is_ok());
+        let names: Vec<&str> = report.services.iter().map(|service| service.name.as_str()).collect();
+        assert_eq!(names, ["config", "pool", "repository"]);
+        assert!(c.try_get::<Repository>("repository").is_ok());
+        assert!(c.build_all().services.is_empty());
+    }
+
+    #[test]
+    fn build_all_collects_every_failure() {
+        let c = &mut Container::new();
+        c.register(Key::of(), |_container: &mut Container| -> Result<Arc<Config>, ContainerError> {
+            Err(ContainerError::builder_failed("config", "file not found"))
+        });
+        c.register(Key::of(), |_container: &mut Container| -> Result<Arc<Cache>, ContainerError> {
+            panic!("cache server unreachable")
+        });
+        c.register("repository", |container: &mut Container| {
+            container.resolve::<Cache>()?;
+            Ok(Arc::new(Repository{_pool: Arc::new(Pool{_config: Arc::new(Config{})})}))
+        });
+        let report = c.build_all();
+        let failures: Vec<String> = report.failures().map(|(name, e)| format!("{}: {}", name, e)).collect();
+        let cache = std::any::type_name::<Cache>();
+        assert_eq!(
+            failures,
+            [
+                format!("repository: building service {} panicked: cache server unreachable", cache),
+                format!("{}: building service {} panicked: cache server unreachable", cache, cache),
+                format!("{}: building service config failed: file not found", std::any::type_name::<Config>()),
+            ]
+        );
+    }
+}